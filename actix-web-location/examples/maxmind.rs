@@ -28,7 +28,7 @@ async fn index(req: HttpRequest, location_provider: Data<MaxMindProvider>) -> im
 async fn main() -> std::io::Result<()> {
     println!("loading MaxMind DB from {MMDB_PATH}");
     let location_provider =
-        MaxMindProvider::from_path(&Path::new(MMDB_PATH)).expect("could not make maxmind client");
+        MaxMindProvider::from_path(Path::new(MMDB_PATH)).expect("could not make maxmind client");
     let location_provider = Data::new(location_provider);
 
     println!("starting HTTP server at http://localhost:8080");
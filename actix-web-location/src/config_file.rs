@@ -0,0 +1,279 @@
+//! Loading a [`LocationConfig`] from a TOML file.
+
+#[cfg(feature = "maxmind")]
+use std::path::PathBuf;
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use anyhow::{anyhow, Context};
+use ipnetwork::IpNetwork;
+use serde::Deserialize;
+
+use crate::{providers::FallbackProvider, Error, Location, LocationConfig, Provider};
+
+#[cfg(feature = "actix-web-v3")]
+use actix_web_3::{error::BlockingError, web};
+
+#[cfg(feature = "actix-web-v4")]
+use actix_web_4::web;
+
+/// The contents of a location configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    providers: Vec<ProviderConfig>,
+
+    #[serde(default)]
+    ip_blocklist: Vec<IpNetwork>,
+
+    /// Time limits for providers in milliseconds, keyed by provider name.
+    #[serde(default)]
+    timeouts: HashMap<String, u64>,
+
+    /// Accepted even without the `cadence` feature, so that a file which
+    /// configures metrics is rejected with a clear error instead of as an
+    /// unknown field.
+    metrics: Option<MetricsConfig>,
+}
+
+/// A provider in a configuration file, distinguished by its `type`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum ProviderConfig {
+    #[cfg(feature = "maxmind")]
+    MaxMind {
+        path: PathBuf,
+        #[serde(default)]
+        schema: crate::providers::Schema,
+        #[serde(default)]
+        return_none_for_private_ips: bool,
+        weight: Option<u32>,
+    },
+    Fallback {
+        name: Option<String>,
+        country: Option<String>,
+        region: Option<String>,
+        city: Option<String>,
+        dma: Option<u16>,
+        weight: Option<u32>,
+    },
+}
+
+/// Where to send StatsD metrics.
+#[derive(Deserialize)]
+#[cfg_attr(not(feature = "cadence"), allow(dead_code))]
+#[serde(deny_unknown_fields)]
+struct MetricsConfig {
+    statsd_host: String,
+    #[serde(default)]
+    prefix: String,
+}
+
+impl LocationConfig {
+    /// Load a configuration from the TOML file at `path`, so the providers
+    /// can be changed without recompiling.
+    ///
+    /// Providers are listed as `[[providers]]` tables, and are consulted in
+    /// the order they are listed. Each has a `type` of `"maxmind"` (with the
+    /// `maxmind` feature) or `"fallback"`, and an optional `weight` that makes
+    /// it a [weighted provider](Self::with_weighted_provider). A `[timeouts]`
+    /// table limits how long each provider, by name, may take in milliseconds
+    /// (see [`with_provider_timeout_map`](Self::with_provider_timeout_map)).
+    /// With the `cadence` feature, a `[metrics]` table sends metrics to a
+    /// StatsD server; without it, a `[metrics]` table is an error.
+    ///
+    /// ```toml
+    /// ip_blocklist = ["192.0.2.0/24"]
+    ///
+    /// [[providers]]
+    /// type = "maxmind"
+    /// path = "/var/lib/GeoLite2-City.mmdb"
+    /// return_none_for_private_ips = true
+    ///
+    /// [[providers]]
+    /// type = "fallback"
+    /// country = "US"
+    ///
+    /// [timeouts]
+    /// maxmind = 50
+    ///
+    /// [metrics]
+    /// statsd_host = "127.0.0.1:8125"
+    /// prefix = "location"
+    /// ```
+    ///
+    /// The file and any databases it refers to are read without blocking the
+    /// async runtime.
+    pub async fn from_config_file(path: &Path) -> Result<Self, Error> {
+        let path = path.to_path_buf();
+        let result = web::block(move || {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("could not read {}", path.display()))
+                .map_err(Error::Setup)?;
+            let file: ConfigFile = toml::from_str(&contents)
+                .with_context(|| format!("invalid location configuration in {}", path.display()))
+                .map_err(Error::Setup)?;
+            file.into_config()
+        })
+        .await;
+
+        #[cfg(feature = "actix-web-v3")]
+        let config = result.map_err(|e| match e {
+            BlockingError::Error(e) => e,
+            BlockingError::Canceled => Error::Setup(anyhow!("configuration loading was canceled")),
+        })?;
+        #[cfg(feature = "actix-web-v4")]
+        let config = result.map_err(|e| Error::Setup(anyhow!("{}", e)))??;
+
+        Ok(config)
+    }
+}
+
+impl ConfigFile {
+    fn into_config(self) -> Result<LocationConfig, Error> {
+        let mut config = LocationConfig::default().with_ip_blocklist(self.ip_blocklist);
+
+        for provider_config in self.providers {
+            let (provider, weight) = provider_config.into_provider()?;
+            config = match weight {
+                Some(weight) => config.with_weighted_provider(provider, weight),
+                None => config.with_provider(provider),
+            };
+        }
+
+        if !self.timeouts.is_empty() {
+            config = config.with_provider_timeout_map(
+                self.timeouts
+                    .into_iter()
+                    .map(|(name, millis)| (name, Duration::from_millis(millis)))
+                    .collect(),
+            );
+        }
+
+        #[cfg(not(feature = "cadence"))]
+        if self.metrics.is_some() {
+            return Err(Error::Setup(anyhow!(
+                "a [metrics] table requires the `cadence` feature"
+            )));
+        }
+
+        #[cfg(feature = "cadence")]
+        if let Some(metrics) = self.metrics {
+            let socket = std::net::UdpSocket::bind("0.0.0.0:0")
+                .context("could not open a socket for metrics")
+                .map_err(Error::Setup)?;
+            let sink = cadence::UdpMetricSink::from(metrics.statsd_host.as_str(), socket)
+                .map_err(|e| Error::Setup(anyhow!("invalid StatsD host: {}", e)))?;
+            config = config.with_metrics(std::sync::Arc::new(cadence::StatsdClient::from_sink(
+                &metrics.prefix,
+                sink,
+            )));
+        }
+
+        Ok(config)
+    }
+}
+
+impl ProviderConfig {
+    fn into_provider(self) -> Result<(Box<dyn Provider>, Option<u32>), Error> {
+        match self {
+            #[cfg(feature = "maxmind")]
+            ProviderConfig::MaxMind {
+                path,
+                schema,
+                return_none_for_private_ips,
+                weight,
+            } => {
+                let provider = crate::providers::MaxMindProvider::build(path)
+                    .with_schema(schema)
+                    .return_none_for_private_ips(return_none_for_private_ips)
+                    .finish()?;
+                Ok((Box::new(provider), weight))
+            }
+            ProviderConfig::Fallback {
+                name,
+                country,
+                region,
+                city,
+                dma,
+                weight,
+            } => {
+                let mut provider = FallbackProvider::new(
+                    Location::build()
+                        .country(country)
+                        .region(region)
+                        .city(city)
+                        .dma(dma),
+                );
+                if let Some(name) = name {
+                    provider = provider.with_name(name);
+                }
+                Ok((Box::new(provider), weight))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::{Error, LocationConfig};
+
+    #[cfg(all(feature = "maxmind", feature = "cadence"))]
+    #[actix_rt::test]
+    async fn load_config_file() {
+        let config = LocationConfig::from_config_file(Path::new("./location-config-test.toml"))
+            .await
+            .expect("could not load configuration");
+
+        assert_eq!(
+            config.providers().collect::<Vec<_>>(),
+            vec!["canary", "maxmind", "fallback"]
+        );
+        let display = config.to_string();
+        assert!(
+            display.starts_with(
+                "LocationConfig: providers=[canary (weight 1), maxmind, fallback], blocked_ip_ranges=2"
+            ),
+            "{}",
+            display
+        );
+        assert!(display.ends_with("metrics=enabled"), "{}", display);
+        let debug = format!("{:?}", config);
+        assert!(
+            debug.contains(r#"provider_timeouts: {"maxmind": 50ms}"#),
+            "{}",
+            debug
+        );
+    }
+
+    #[cfg(not(feature = "cadence"))]
+    #[actix_rt::test]
+    async fn metrics_require_cadence() {
+        let directory = std::env::temp_dir();
+        let path = directory.join(format!("location-metrics-{}.toml", std::process::id()));
+        std::fs::write(&path, "[metrics]\nstatsd_host = \"127.0.0.1:8125\"\n").unwrap();
+
+        let result = LocationConfig::from_config_file(&path).await;
+        std::fs::remove_file(&path).unwrap();
+        match result {
+            Err(Error::Setup(e)) => assert!(e.to_string().contains("cadence"), "{}", e),
+            other => panic!("expected a setup error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn invalid_config_file() {
+        let directory = std::env::temp_dir();
+        let path = directory.join(format!("location-config-{}.toml", std::process::id()));
+        std::fs::write(&path, "[[providers]]\ntype = \"cloudflare\"\n").unwrap();
+
+        let result = LocationConfig::from_config_file(&path).await;
+        assert!(matches!(result, Err(Error::Setup(_))));
+        std::fs::remove_file(&path).unwrap();
+
+        let result = LocationConfig::from_config_file(Path::new("./missing.toml")).await;
+        assert!(matches!(result, Err(Error::Setup(_))));
+    }
+}
@@ -1,32 +1,123 @@
+use std::{
+    net::IpAddr,
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    iso3166::{
+        COUNTRY_NAMES, EEA_NON_EU, EUROPEAN_UNION, LANGUAGE_HINTS, REGION_LANGUAGE_HINTS,
+        REGION_NAMES,
+    },
+    Error, Provider,
+};
+use anyhow::anyhow;
 #[cfg(feature = "maxmind")]
-use maxminddb::geoip2::City;
+use maxminddb::geoip2::{City, Enterprise};
 #[cfg(feature = "serde")]
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "actix-web-v3")]
+use actix_web_3::http::HeaderMap;
+#[cfg(feature = "actix-web-v4")]
+use actix_web_4::http::header::HeaderMap;
 
 /// The location information that providers must produce.
-#[derive(Debug, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(Serialize))]
+///
+/// Outside of this crate, locations can only be created with
+/// [`Location::build`], so that fields can be added without breaking
+/// changes. Code that created locations with struct literals should use the
+/// builder instead. The fields are deprecated and will become private in a
+/// future release, so read them with the accessor methods such as
+/// [`Location::country_ref`] or [`Location::latitude`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub struct Location {
     /// Country in ISO 3166-1 alpha-2 format, such as "MX" for Mexico or "IT" for Italy.
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::country` or `Location::country_ref` instead")]
     pub country: Option<String>,
 
     /// Region/region (e.g. a US state) in ISO 3166-2 format, such as "QC"
     /// for Quebec (with country = "CA") or "TX" for Texas (with country = "US").
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::region` or `Location::region_ref` instead")]
     pub region: Option<String>,
 
+    /// The name of the region, such as "Quebec", if the provider knows it.
+    /// See [`Location::region_name`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub(crate) region_name: Option<String>,
+
     /// City, listed by name such as "Portland" or "Berlin".
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::city` or `Location::city_ref` instead")]
     pub city: Option<String>,
 
     /// The Designated Market Area code, as defined by [Nielsen]. Only defined in the US.
     ///
     /// [Nielsen]: https://www.nielsen.com/us/en/contact-us/intl-campaigns/dma-maps/
     #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::dma` instead")]
     pub dma: Option<u16>,
 
+    /// A market area code for locations outside the US, where [`dma`] isn't
+    /// defined, such as a media market territory in another country. Its
+    /// meaning depends on the provider and the country.
+    ///
+    /// [`dma`]: Self::dma
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::market_area_code` instead")]
+    pub market_area_code: Option<u32>,
+
+    /// Latitude in decimal degrees, as defined by WGS 84.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::latitude` instead")]
+    pub latitude: Option<f64>,
+
+    /// Longitude in decimal degrees, as defined by WGS 84.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::longitude` instead")]
+    pub longitude: Option<f64>,
+
+    /// The radius in kilometers around `latitude` and `longitude` that the
+    /// location is expected to be within.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::accuracy_radius` instead")]
+    pub accuracy_radius: Option<u16>,
+
+    /// The IANA time zone, such as "America/Los_Angeles".
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::timezone_ref` instead")]
+    pub timezone: Option<String>,
+
+    /// The name of the Internet Service Provider, such as "Comcast Cable".
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::isp_ref` instead")]
+    pub isp: Option<String>,
+
+    /// The type of network connection, such as "Cable/DSL" or "Cellular".
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::connection_type_ref` instead")]
+    pub connection_type: Option<String>,
+
     /// The name of the provider that produced this recommendation.
+    #[doc(hidden)]
+    #[deprecated(note = "use `Location::provider` instead")]
     pub provider: String,
 }
 
@@ -51,24 +142,334 @@ macro_rules! location_field {
     };
 }
 
+/// The provider used by [`Location::from_ip`].
+static GLOBAL_PROVIDER: OnceLock<Arc<dyn Provider>> = OnceLock::new();
+
+/// Set the provider used by [`Location::from_ip`], unless it is already set.
+pub(crate) fn set_global_provider(provider: Arc<dyn Provider>) -> Result<(), Error> {
+    GLOBAL_PROVIDER
+        .set(provider)
+        .map_err(|_| Error::Setup(anyhow!("the global location provider is already set")))
+}
+
+// The fields are deprecated for users of the crate, not for its own methods.
+#[allow(deprecated)]
 impl Location {
     /// Create a builder for a [`Location`] that can be assembled incrementally.
     pub fn build() -> LocationBuilder {
         LocationBuilder::default()
     }
 
+    /// Look up the location of `addr` with the global provider, for code
+    /// outside of request handlers such as background jobs. The provider is
+    /// set with [`Location::set_global_provider`] or
+    /// [`LocationConfig::as_global_provider`](crate::LocationConfig::as_global_provider),
+    /// and this returns an [`Error::Setup`] if neither was called.
+    ///
+    /// Only [`Provider::get_location_for_ip`] is used, so providers that need
+    /// more of the request than the address return `None`.
+    pub async fn from_ip(addr: IpAddr) -> Result<Option<Location>, Error> {
+        let provider = GLOBAL_PROVIDER
+            .get()
+            .ok_or_else(|| Error::Setup(anyhow!("no global location provider has been set")))?;
+        provider.get_location_for_ip(addr).await
+    }
+
+    /// Set the provider used by [`Location::from_ip`].
+    ///
+    /// The global provider can only be set once for the whole process, and
+    /// this returns an [`Error::Setup`] if it was already set. It is separate
+    /// from any [`LocationConfig`](crate::LocationConfig), so the IP
+    /// blocklist, other providers, metrics, and so on don't apply to it.
+    pub fn set_global_provider(provider: impl Provider + 'static) -> Result<(), Error> {
+        set_global_provider(Arc::new(provider))
+    }
+
     location_field!(country, String);
     location_field!(region, String);
     location_field!(city, String);
     location_field!(dma, u16);
+    location_field!(market_area_code, u32);
+
+    /// Borrow the country, without allocating a copy like [`Location::country`].
+    pub fn country_ref(&self) -> Option<&str> {
+        self.country.as_deref()
+    }
+
+    /// Borrow the region, without allocating a copy like [`Location::region`].
+    pub fn region_ref(&self) -> Option<&str> {
+        self.region.as_deref()
+    }
+
+    /// Borrow the city, without allocating a copy like [`Location::city`].
+    pub fn city_ref(&self) -> Option<&str> {
+        self.city.as_deref()
+    }
+
+    /// Latitude in decimal degrees, as defined by WGS 84.
+    pub fn latitude(&self) -> Option<f64> {
+        self.latitude
+    }
+
+    /// Longitude in decimal degrees, as defined by WGS 84.
+    pub fn longitude(&self) -> Option<f64> {
+        self.longitude
+    }
+
+    /// The radius in kilometers around the latitude and longitude that the
+    /// location is expected to be within.
+    pub fn accuracy_radius(&self) -> Option<u16> {
+        self.accuracy_radius
+    }
+
+    /// Borrow the IANA time zone, such as "America/Los_Angeles".
+    pub fn timezone_ref(&self) -> Option<&str> {
+        self.timezone.as_deref()
+    }
+
+    /// Borrow the name of the Internet Service Provider, such as "Comcast Cable".
+    pub fn isp_ref(&self) -> Option<&str> {
+        self.isp.as_deref()
+    }
+
+    /// Borrow the type of network connection, such as "Cable/DSL" or "Cellular".
+    pub fn connection_type_ref(&self) -> Option<&str> {
+        self.connection_type.as_deref()
+    }
+
+    /// The name of the provider that produced this location.
+    pub fn provider(&self) -> &str {
+        &self.provider
+    }
+
+    /// The English name of the country, such as "United States" for "US".
+    ///
+    /// Returns `None` if the country is unknown or isn't an ISO 3166-1 alpha-2 code.
+    pub fn country_name(&self) -> Option<&'static str> {
+        self.country
+            .as_deref()
+            .and_then(|code| COUNTRY_NAMES.get(code).copied())
+    }
+
+    /// Is the country a member state of the European Union? Returns `None` if
+    /// the country is unknown.
+    pub fn is_in_european_union(&self) -> Option<bool> {
+        self.country
+            .as_deref()
+            .map(|code| EUROPEAN_UNION.contains(code))
+    }
+
+    /// Is the country part of the European Economic Area, which is the EU as
+    /// well as Iceland, Liechtenstein, and Norway? Returns `None` if the
+    /// country is unknown.
+    pub fn is_in_eea(&self) -> Option<bool> {
+        self.country
+            .as_deref()
+            .map(|code| EUROPEAN_UNION.contains(code) || EEA_NON_EU.contains(code))
+    }
+
+    /// The great-circle distance in kilometers between this location and
+    /// `other`, using the Haversine formula.
+    ///
+    /// Returns `None` if either location doesn't have coordinates.
+    pub fn geocode_distance_km(&self, other: &Location) -> Option<f64> {
+        /// The mean radius of the Earth, in kilometers.
+        const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+        let (lat1, lon1) = (self.latitude?.to_radians(), self.longitude?.to_radians());
+        let (lat2, lon2) = (other.latitude?.to_radians(), other.longitude?.to_radians());
+
+        let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+        Some(2.0 * EARTH_RADIUS_KM * a.sqrt().asin())
+    }
+
+    /// The current offset from UTC in hours of the location's time zone, such
+    /// as `-7.0` for "America/Los_Angeles" during daylight saving time.
+    ///
+    /// Returns `None` if the time zone is unknown or isn't a valid IANA time zone.
+    #[cfg(feature = "timezone")]
+    pub fn time_offset_hours(&self) -> Option<f32> {
+        use chrono::{Offset, TimeZone, Utc};
+
+        let tz: chrono_tz::Tz = self.timezone.as_deref()?.parse().ok()?;
+        let offset = tz.offset_from_utc_datetime(&Utc::now().naive_utc());
+        Some(offset.fix().local_minus_utc() as f32 / 3600.0)
+    }
+
+    /// The name of the region, such as "California" for "CA" in the "US".
+    ///
+    /// Providers that know the name of the region, such as the MaxMind
+    /// providers in their preferred language, set it with
+    /// [`LocationBuilder::region_name`]. Otherwise the English name is looked
+    /// up from the region code, which is only known for subdivisions of the
+    /// United States and Canada. Returns `None` if the country or region is
+    /// unknown, or if no name is known for the region.
+    pub fn region_name(&self) -> Option<&str> {
+        let region = self.region.as_deref()?;
+        if let Some(name) = self.region_name.as_deref() {
+            return Some(name);
+        }
+        let country = self.country.as_deref()?;
+        REGION_NAMES
+            .get(format!("{}-{}", country, region).as_str())
+            .copied()
+    }
+
+    /// A best guess at an `Accept-Language` header value for someone at this
+    /// location, such as `"de"` for Germany or `"en,fr;q=0.9"` for Canada.
+    /// This is useful when proxying requests that don't have their own
+    /// `Accept-Language` header.
+    ///
+    /// The region is used where its languages differ from the rest of the
+    /// country, such as Quebec. Returns `None` if the country is unknown or
+    /// has no hint.
+    pub fn to_accept_language_hint(&self) -> Option<String> {
+        let country = self.country.as_deref()?;
+        self.region
+            .as_deref()
+            .and_then(|region| {
+                REGION_LANGUAGE_HINTS
+                    .get(format!("{}-{}", country, region).as_str())
+                    .copied()
+            })
+            .or_else(|| LANGUAGE_HINTS.get(country).copied())
+            .map(str::to_string)
+    }
+
+    /// Combine this location with `other`, using the fields of `other` only
+    /// where this location doesn't have a value. The provider of this location
+    /// is kept.
+    pub fn merge(self, other: Location) -> Location {
+        // The region name belongs to the region it was given with.
+        let (region, region_name) = if self.region.is_some() {
+            (self.region, self.region_name)
+        } else {
+            (other.region, other.region_name)
+        };
+        Location {
+            country: self.country.or(other.country),
+            region,
+            region_name,
+            city: self.city.or(other.city),
+            dma: self.dma.or(other.dma),
+            market_area_code: self.market_area_code.or(other.market_area_code),
+            latitude: self.latitude.or(other.latitude),
+            longitude: self.longitude.or(other.longitude),
+            accuracy_radius: self.accuracy_radius.or(other.accuracy_radius),
+            timezone: self.timezone.or(other.timezone),
+            isp: self.isp.or(other.isp),
+            connection_type: self.connection_type.or(other.connection_type),
+            provider: self.provider,
+        }
+    }
+
+    /// Parse a location from the JSON serialization of a browser [Geolocation
+    /// API] position, such as `{"coords": {"latitude": 45.5, "longitude":
+    /// -122.6, "accuracy": 20.0}}`.
+    ///
+    /// Only `latitude`, `longitude`, and `accuracy_radius` are populated. The
+    /// browser reports accuracy in meters, which is rounded up to the nearest
+    /// kilometer.
+    ///
+    /// [Geolocation API]: https://developer.mozilla.org/en-US/docs/Web/API/GeolocationPosition
+    #[cfg(feature = "serde")]
+    pub fn from_geolocation_api_response(
+        json: &str,
+        provider: impl Into<String>,
+    ) -> Result<Location, serde_json::Error> {
+        let position: GeolocationPosition = serde_json::from_str(json)?;
+        Ok(Location {
+            country: None,
+            region: None,
+            region_name: None,
+            city: None,
+            dma: None,
+            market_area_code: None,
+            latitude: Some(position.coords.latitude),
+            longitude: Some(position.coords.longitude),
+            accuracy_radius: position
+                .coords
+                .accuracy
+                .map(|meters| (meters / 1000.0).ceil() as u16),
+            timezone: None,
+            isp: None,
+            connection_type: None,
+            provider: provider.into(),
+        })
+    }
+
+    /// Start a location from the viewer headers that AWS CloudFront adds to
+    /// requests: `CloudFront-Viewer-Country`, `CloudFront-Viewer-Country-Region`
+    /// and `CloudFront-Viewer-City`. Missing headers are left empty, and the
+    /// provider is set to `"cloudfront"`.
+    ///
+    /// `CloudFront-Viewer-Postal-Code` is not used, since locations don't
+    /// include postal codes.
+    #[cfg(any(feature = "actix-web-v3", feature = "actix-web-v4"))]
+    pub fn from_cloudfront_headers(headers: &HeaderMap) -> LocationBuilder {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        Location::build()
+            .country(header("CloudFront-Viewer-Country"))
+            .region(header("CloudFront-Viewer-Country-Region"))
+            .city(header("CloudFront-Viewer-City"))
+            .provider("cloudfront".to_string())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<serde_json::Value> for Location {
+    type Error = serde_json::Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        serde_json::from_value(value)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Location> for serde_json::Value {
+    fn from(location: Location) -> Self {
+        serde_json::to_value(location).expect("Location serialization bug")
+    }
+}
+
+/// The subset of a browser `GeolocationPosition` that is used to build a [`Location`].
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct GeolocationPosition {
+    coords: GeolocationCoordinates,
+}
+
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct GeolocationCoordinates {
+    latitude: f64,
+    longitude: f64,
+    accuracy: Option<f64>,
 }
 
 #[derive(Default)]
 pub struct LocationBuilder {
     country: Option<String>,
     region: Option<String>,
+    region_name: Option<String>,
     city: Option<String>,
     dma: Option<u16>,
+    market_area_code: Option<u32>,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    accuracy_radius: Option<u16>,
+    timezone: Option<String>,
+    isp: Option<String>,
+    connection_type: Option<String>,
     provider: Option<String>,
 }
 
@@ -84,53 +485,151 @@ macro_rules! builder_field {
 impl LocationBuilder {
     builder_field!(country, String);
     builder_field!(region, String);
+    builder_field!(region_name, String);
     builder_field!(city, String);
     builder_field!(dma, u16);
+    builder_field!(market_area_code, u32);
+    builder_field!(latitude, f64);
+    builder_field!(longitude, f64);
+    builder_field!(accuracy_radius, u16);
+    builder_field!(timezone, String);
+    builder_field!(isp, String);
+    builder_field!(connection_type, String);
     builder_field!(provider, String);
 
+    #[allow(deprecated)]
     pub fn finish(self) -> Result<Location, ()> {
         Ok(Location {
             country: self.country,
             region: self.region,
+            region_name: self.region_name,
             city: self.city,
             dma: self.dma,
+            market_area_code: self.market_area_code,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            accuracy_radius: self.accuracy_radius,
+            timezone: self.timezone,
+            isp: self.isp,
+            connection_type: self.connection_type,
             provider: self.provider.ok_or(())?,
         })
     }
 }
 
+/// Split a MaxMind metro code into a DMA code for locations in the US, or a
+/// market area code for locations elsewhere.
+#[cfg(feature = "maxmind")]
+fn split_metro_code(country: Option<&str>, metro_code: Option<u16>) -> (Option<u16>, Option<u32>) {
+    if country == Some("US") {
+        (metro_code, None)
+    } else {
+        (None, metro_code.map(u32::from))
+    }
+}
+
 #[cfg(feature = "maxmind")]
 impl<'a> From<(City<'a>, &str)> for LocationBuilder {
     fn from((val, preferred_language): (City<'a>, &str)) -> Self {
+        let (dma, market_area_code) = split_metro_code(
+            val.country.as_ref().and_then(|country| country.iso_code),
+            val.location
+                .as_ref()
+                .and_then(|location| location.metro_code),
+        );
+
+        // Subdivisions are listed in least-specific order. In the US, this might mean that subdivisions is state and then county. We want only the first.
+        let subdivision = val
+            .subdivisions
+            .as_ref()
+            .and_then(|subdivisions| subdivisions.first());
+        let region = subdivision.and_then(|subdivision| subdivision.iso_code);
+        let region_name = subdivision
+            .and_then(|subdivision| subdivision.names.as_ref())
+            .and_then(|names| names.get(preferred_language));
+
         Location::build()
             .country(
                 val.country
                     .and_then(|country| country.iso_code)
                     .map(String::from),
             )
-            .region(
-                val.subdivisions
-                    // Subdivisions are listed in least-specific order. In the US, this might mean that subdivisions is state and then county. We want only the first.
-                    .and_then(|subdivisions| {
-                        subdivisions
-                            .get(0)
-                            .and_then(|subdivision| subdivision.iso_code)
-                    })
-                    .map(ToString::to_string),
-            )
+            .region(region.map(String::from))
+            .region_name(region_name.map(|name| name.to_string()))
             .city(
                 val.city
                     .and_then(|city| city.names)
                     .and_then(|names| names.get(preferred_language).map(|name| name.to_string()))
                     .map(|name| (*name).to_string()),
             )
-            .dma(val.location.and_then(|location| location.metro_code))
+            .dma(dma)
+            .market_area_code(market_area_code)
+            .timezone(
+                val.location
+                    .and_then(|location| location.time_zone)
+                    .map(String::from),
+            )
+    }
+}
+
+#[cfg(feature = "maxmind")]
+impl<'a> From<(Enterprise<'a>, &str)> for LocationBuilder {
+    fn from((val, preferred_language): (Enterprise<'a>, &str)) -> Self {
+        let (isp, connection_type) = val
+            .traits
+            .map(|traits| (traits.isp, traits.connection_type))
+            .unwrap_or_default();
+        let (dma, market_area_code) = split_metro_code(
+            val.country.as_ref().and_then(|country| country.iso_code),
+            val.location
+                .as_ref()
+                .and_then(|location| location.metro_code),
+        );
+
+        // As with City, only the least specific subdivision is used.
+        let subdivision = val
+            .subdivisions
+            .as_ref()
+            .and_then(|subdivisions| subdivisions.first());
+        let region = subdivision.and_then(|subdivision| subdivision.iso_code);
+        let region_name = subdivision
+            .and_then(|subdivision| subdivision.names.as_ref())
+            .and_then(|names| names.get(preferred_language));
+
+        Location::build()
+            .country(
+                val.country
+                    .and_then(|country| country.iso_code)
+                    .map(String::from),
+            )
+            .region(region.map(String::from))
+            .region_name(region_name.map(|name| name.to_string()))
+            .city(
+                val.city
+                    .and_then(|city| city.names)
+                    .and_then(|names| names.get(preferred_language).map(|name| name.to_string())),
+            )
+            .dma(dma)
+            .market_area_code(market_area_code)
+            .timezone(
+                val.location
+                    .and_then(|location| location.time_zone)
+                    .map(String::from),
+            )
+            .isp(isp.map(String::from))
+            .connection_type(connection_type.map(String::from))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::Location;
+    use crate::{providers::FallbackProvider, Error, LocationConfig};
+
+    #[cfg(not(feature = "actix-web-v4"))]
+    use actix_web_3::test::TestRequest;
+    #[cfg(feature = "actix-web-v4")]
+    use actix_web_4::test::TestRequest;
 
     #[test]
     fn builder_works() {
@@ -145,13 +644,14 @@ mod tests {
 
         assert_eq!(
             location,
-            Location {
-                country: Some("US".to_string()),
-                region: Some("OR".to_string()),
-                city: Some("Portland".to_string()),
-                dma: Some(810),
-                provider: "test".to_string()
-            }
+            Location::build()
+                .provider("test".to_string())
+                .dma(810)
+                .city("Portland".to_string())
+                .region("OR".to_string())
+                .country("US".to_string())
+                .finish()
+                .unwrap()
         );
     }
 
@@ -183,31 +683,445 @@ mod tests {
         assert_eq!(location.region(), "");
         assert_eq!(location.city(), "");
         assert_eq!(location.dma(), 0);
+        assert_eq!(location.market_area_code(), 0);
     }
 
-    #[cfg(maxmind)]
-    #[actix_rt::test]
-    async fn known_ip() {
-        use maxminddb::geoip2::model::City;
+    #[test]
+    fn ref_methods() {
+        let location = Location::build()
+            .country("US".to_string())
+            .region("CA".to_string())
+            .city("Sunnyvale".to_string())
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
+
+        assert_eq!(location.country_ref(), Some("US"));
+        assert_eq!(location.region_ref(), Some("CA"));
+        assert_eq!(location.city_ref(), Some("Sunnyvale"));
+
+        let location = Location::build()
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
+
+        assert_eq!(location.country_ref(), None);
+        assert_eq!(location.region_ref(), None);
+        assert_eq!(location.city_ref(), None);
+    }
+
+    #[test]
+    fn market_area_code() {
+        let location = Location::build()
+            .country("CA".to_string())
+            .market_area_code(9_001u32)
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
+
+        assert_eq!(location.market_area_code(), 9_001);
+        assert_eq!(location.dma(), 0);
+    }
+
+    #[cfg(feature = "maxmind")]
+    #[test]
+    fn split_metro_code() {
+        use super::split_metro_code;
+
+        assert_eq!(split_metro_code(Some("US"), Some(819)), (Some(819), None));
+        assert_eq!(split_metro_code(Some("CA"), Some(819)), (None, Some(819)));
+        assert_eq!(split_metro_code(None, Some(819)), (None, Some(819)));
+        assert_eq!(split_metro_code(Some("US"), None), (None, None));
+    }
+
+    #[test]
+    fn country_name() {
+        let location = |country: Option<&str>| {
+            Location::build()
+                .country(country.map(String::from))
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        };
+
+        assert_eq!(location(Some("US")).country_name(), Some("United States"));
+        assert_eq!(location(Some("MX")).country_name(), Some("Mexico"));
+        assert_eq!(location(Some("XX")).country_name(), None);
+        assert_eq!(location(None).country_name(), None);
+        assert_eq!(super::COUNTRY_NAMES.len(), 249);
+    }
+
+    #[test]
+    fn european_union_and_eea() {
+        let location = |country: Option<&str>| {
+            Location::build()
+                .country(country.map(String::from))
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        };
+
+        assert_eq!(location(Some("FR")).is_in_european_union(), Some(true));
+        assert_eq!(location(Some("FR")).is_in_eea(), Some(true));
+        assert_eq!(location(Some("NO")).is_in_european_union(), Some(false));
+        assert_eq!(location(Some("NO")).is_in_eea(), Some(true));
+        assert_eq!(location(Some("US")).is_in_european_union(), Some(false));
+        assert_eq!(location(Some("US")).is_in_eea(), Some(false));
+        assert_eq!(location(None).is_in_european_union(), None);
+        assert_eq!(location(None).is_in_eea(), None);
+    }
+
+    #[cfg(feature = "timezone")]
+    #[test]
+    fn time_offset_hours() {
+        let location = |timezone: Option<&str>| {
+            Location::build()
+                .timezone(timezone.map(String::from))
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        };
+
+        let los_angeles = location(Some("America/Los_Angeles"))
+            .time_offset_hours()
+            .expect("should have an offset");
+        assert!(
+            los_angeles == -8.0 || los_angeles == -7.0,
+            "unexpected offset {}",
+            los_angeles
+        );
+        assert_eq!(
+            location(Some("Asia/Kolkata")).time_offset_hours(),
+            Some(5.5)
+        );
+        assert_eq!(location(Some("Not/A_Zone")).time_offset_hours(), None);
+        assert_eq!(location(None).time_offset_hours(), None);
+    }
+
+    #[test]
+    fn geocode_distance_km() {
+        let location = |latitude: Option<f64>, longitude: Option<f64>| {
+            Location::build()
+                .latitude(latitude)
+                .longitude(longitude)
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        };
+        let portland = location(Some(45.5152), Some(-122.6784));
+        let berlin = location(Some(52.5200), Some(13.4050));
+        let london = location(Some(51.5074), Some(-0.1278));
+        let paris = location(Some(48.8566), Some(2.3522));
+
+        let distance = portland.geocode_distance_km(&berlin).unwrap();
+        assert!((distance - 8338.3).abs() < 1.0, "got {}", distance);
+        let distance = london.geocode_distance_km(&paris).unwrap();
+        assert!((distance - 343.6).abs() < 1.0, "got {}", distance);
+        assert_eq!(
+            berlin.geocode_distance_km(&portland),
+            portland.geocode_distance_km(&berlin)
+        );
+        assert_eq!(portland.geocode_distance_km(&portland), Some(0.0));
+
+        assert_eq!(portland.geocode_distance_km(&location(None, None)), None);
+        assert_eq!(location(Some(1.0), None).geocode_distance_km(&berlin), None);
+    }
+
+    #[test]
+    fn region_name() {
+        let location = |country: &str, region: &str| {
+            Location::build()
+                .country(country.to_string())
+                .region(region.to_string())
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        };
+
+        assert_eq!(location("US", "CA").region_name(), Some("California"));
+        assert_eq!(location("US", "OR").region_name(), Some("Oregon"));
+        assert_eq!(location("US", "TX").region_name(), Some("Texas"));
+        assert_eq!(location("US", "NY").region_name(), Some("New York"));
+        assert_eq!(location("US", "WA").region_name(), Some("Washington"));
+        assert_eq!(location("CA", "QC").region_name(), Some("Quebec"));
+        assert_eq!(location("CA", "BC").region_name(), Some("British Columbia"));
+        assert_eq!(location("CA", "ON").region_name(), Some("Ontario"));
+        assert_eq!(location("CA", "AB").region_name(), Some("Alberta"));
+        assert_eq!(location("CA", "NS").region_name(), Some("Nova Scotia"));
+
+        // The region code alone is ambiguous without the country.
+        assert_eq!(location("CA", "CA").region_name(), None);
+        assert_eq!(location("FR", "IDF").region_name(), None);
+
+        let no_country = Location::build()
+            .region("CA".to_string())
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
+        assert_eq!(no_country.region_name(), None);
+
+        // Names from the provider are used for any country.
+        let named = Location::build()
+            .country("FR".to_string())
+            .region("IDF".to_string())
+            .region_name("Île-de-France".to_string())
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
+        assert_eq!(named.region_name(), Some("Île-de-France"));
+        assert_eq!(
+            location("FR", "NOR").merge(named.clone()).region_name(),
+            None,
+            "the name should only be used with its own region"
+        );
+        let no_region = Location::build()
+            .country("FR".to_string())
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
+        assert_eq!(no_region.merge(named).region_name(), Some("Île-de-France"));
+    }
+
+    #[test]
+    fn accept_language_hint() {
+        let location = |country: Option<&str>, region: Option<&str>| {
+            Location::build()
+                .country(country.map(str::to_string))
+                .region(region.map(str::to_string))
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        };
+
+        assert_eq!(
+            location(Some("DE"), None).to_accept_language_hint(),
+            Some("de".to_string())
+        );
+        assert_eq!(
+            location(Some("CA"), Some("ON")).to_accept_language_hint(),
+            Some("en,fr;q=0.9".to_string())
+        );
+        assert_eq!(
+            location(Some("CA"), Some("QC")).to_accept_language_hint(),
+            Some("fr,en;q=0.9".to_string())
+        );
+        assert_eq!(
+            location(Some("CH"), None).to_accept_language_hint(),
+            Some("de,fr;q=0.9,it;q=0.8".to_string())
+        );
+        assert_eq!(location(Some("AQ"), None).to_accept_language_hint(), None);
+        assert_eq!(location(None, Some("QC")).to_accept_language_hint(), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_value_round_trip() {
+        let location = Location::build()
+            .country("US".to_string())
+            .region("OR".to_string())
+            .city("Portland".to_string())
+            .dma(810)
+            .latitude(45.5152)
+            .longitude(-122.6784)
+            .accuracy_radius(5)
+            .provider("test".to_string())
+            .finish()
+            .unwrap();
 
+        let value = serde_json::Value::from(location.clone());
+        assert_eq!(value, serde_json::to_value(&location).unwrap());
+        assert_eq!(Location::try_from(value).unwrap(), location);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn json_value_missing_fields() {
+        let value = serde_json::json!({ "country": "CA", "provider": "test" });
+        assert_eq!(
+            Location::try_from(value.clone()).unwrap(),
+            Location::build()
+                .country("CA".to_string())
+                .provider("test".to_string())
+                .finish()
+                .unwrap()
+        );
+        assert_eq!(
+            serde_json::Value::from(Location::try_from(value.clone()).unwrap()),
+            value
+        );
+
+        assert!(Location::try_from(serde_json::json!({ "country": "CA" })).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn from_geolocation_api_response() {
+        let json = r#"{
+            "coords": {
+                "latitude": 45.5152,
+                "longitude": -122.6784,
+                "altitude": null,
+                "accuracy": 1250.5,
+                "altitudeAccuracy": null,
+                "heading": null,
+                "speed": null
+            },
+            "timestamp": 1660000000000
+        }"#;
+
+        let location = Location::from_geolocation_api_response(json, "browser")
+            .expect("could not parse geolocation response");
+
+        assert_eq!(
+            location,
+            Location::build()
+                .latitude(45.5152)
+                .longitude(-122.6784)
+                .accuracy_radius(2u16)
+                .provider("browser".to_string())
+                .finish()
+                .unwrap()
+        );
+    }
+
+    #[cfg(feature = "maxmind")]
+    #[test]
+    fn known_ip() {
+        use maxminddb::geoip2::City;
+
+        use super::LocationBuilder;
         use crate::providers::tests::maxmind::{MMDB_LOC, TEST_ADDR_1};
 
-        let mmdb = maxminddb::Reader::open_readfile(path)
-            .map_err(|e| Error::Setup(anyhow!("{}", e)))
-            .expect("could not create mmdb");
-        let db_value = mmdb.lookup::<City>(TEST_ADDR_1);
-        let location: Location = (db_value, "en").into();
+        let mmdb = maxminddb::Reader::open_readfile(MMDB_LOC).expect("could not open mmdb");
+        let city: City = mmdb
+            .lookup(TEST_ADDR_1.parse().unwrap())
+            .expect("could not look up test address");
+        let location = LocationBuilder::from((city, "en"))
+            .provider("maxmind".to_string())
+            .finish()
+            .expect("bug when creating location");
 
         assert_eq!(
             location,
             Location::build()
                 .country("US".to_string())
                 .region("WA".to_string())
+                .region_name("Washington".to_string())
                 .city("Milton".to_string())
                 .dma(819)
+                .timezone("America/Los_Angeles".to_string())
                 .provider("maxmind".to_string())
                 .finish()
                 .expect("bug when creating location")
         );
     }
+
+    #[test]
+    fn merge() {
+        let city = Location::build()
+            .country("US".to_string())
+            .city("Milton".to_string())
+            .provider("city".to_string())
+            .finish()
+            .unwrap();
+        let asn = Location::build()
+            .country("CA".to_string())
+            .isp("Example ISP".to_string())
+            .provider("asn".to_string())
+            .finish()
+            .unwrap();
+
+        let merged = city.merge(asn);
+        assert_eq!(merged.country(), "US", "existing fields should be kept");
+        assert_eq!(merged.city(), "Milton");
+        assert_eq!(merged.isp_ref(), Some("Example ISP"));
+        assert_eq!(merged.provider(), "city");
+    }
+
+    #[test]
+    fn from_cloudfront_headers() {
+        let headers = [
+            ("CloudFront-Viewer-Country", "US"),
+            ("CloudFront-Viewer-Country-Region", "WA"),
+            ("CloudFront-Viewer-City", "Seattle"),
+            ("CloudFront-Viewer-Postal-Code", "98101"),
+        ];
+        let mut request = TestRequest::default();
+        for header in headers {
+            #[cfg(not(feature = "actix-web-v4"))]
+            {
+                request = request.header(header.0, header.1);
+            }
+            #[cfg(feature = "actix-web-v4")]
+            {
+                request = request.insert_header(header);
+            }
+        }
+        let request = request.to_http_request();
+
+        let location = Location::from_cloudfront_headers(request.headers())
+            .finish()
+            .unwrap();
+        assert_eq!(location.country_ref(), Some("US"));
+        assert_eq!(location.region_ref(), Some("WA"));
+        assert_eq!(location.city_ref(), Some("Seattle"));
+        assert_eq!(location.provider(), "cloudfront");
+    }
+
+    #[test]
+    fn from_cloudfront_headers_partial() {
+        #[cfg(not(feature = "actix-web-v4"))]
+        let request = TestRequest::default()
+            .header("CloudFront-Viewer-Country", "DE")
+            .to_http_request();
+        #[cfg(feature = "actix-web-v4")]
+        let request = TestRequest::default()
+            .insert_header(("CloudFront-Viewer-Country", "DE"))
+            .to_http_request();
+
+        let location = Location::from_cloudfront_headers(request.headers())
+            .dma(1)
+            .finish()
+            .unwrap();
+        assert_eq!(location.country(), "DE");
+        assert_eq!(location.region_ref(), None);
+        assert_eq!(location.city_ref(), None);
+        assert_eq!(location.dma(), 1);
+    }
+
+    // The global provider can only be set once per process, so it is only
+    // tested here.
+    #[actix_rt::test]
+    async fn global_provider() {
+        let addr = "192.0.2.1".parse().unwrap();
+        assert!(matches!(
+            Location::from_ip(addr).await,
+            Err(Error::Setup(_))
+        ));
+        assert!(matches!(
+            LocationConfig::default().as_global_provider(),
+            Err(Error::Setup(_))
+        ));
+
+        let config = LocationConfig::default()
+            .with_provider(FallbackProvider::new(
+                Location::build().country("CA".to_string()),
+            ))
+            .with_provider(FallbackProvider::new(
+                Location::build().country("US".to_string()),
+            ));
+        config
+            .as_global_provider()
+            .expect("couldn't set the provider");
+        let location = Location::from_ip(addr)
+            .await
+            .expect("error looking up the address")
+            .expect("the fallback should find a location");
+        assert_eq!(location.country(), "CA");
+
+        assert!(matches!(
+            Location::set_global_provider(FallbackProvider::new(Location::build())),
+            Err(Error::Setup(_))
+        ));
+    }
 }
@@ -20,6 +20,87 @@ pub enum Error {
 
     #[error("problem converting provider response to a location")]
     Conversion(#[source] anyhow::Error),
+
+    #[error("{} providers failed", .0.len())]
+    Multiple(Vec<Error>),
 }
 
 impl ResponseError for Error {}
+
+impl Error {
+    /// The source of this error. For [`Error::Multiple`], this is the source
+    /// of the first error.
+    fn source_error(&self) -> Option<&anyhow::Error> {
+        match self {
+            Error::Setup(source)
+            | Error::Provider(source)
+            | Error::Http(source)
+            | Error::Conversion(source) => Some(source),
+            Error::Multiple(errors) => errors.first().and_then(Error::source_error),
+        }
+    }
+
+    /// The innermost error that caused this error, such as the
+    /// `maxminddb::MaxMindDBError` behind an [`Error::Provider`]. Use
+    /// `downcast_ref` to check for specific error types.
+    ///
+    /// For [`Error::Multiple`], this is the root cause of the first error.
+    pub fn root_cause(&self) -> &(dyn std::error::Error + 'static) {
+        match self.source_error() {
+            Some(source) => source.root_cause(),
+            None => self,
+        }
+    }
+
+    /// Iterate over the errors that caused this error, from the outermost to
+    /// the innermost. This does not include this error itself.
+    ///
+    /// For [`Error::Multiple`], these are the causes of the first error.
+    pub fn causes(&self) -> impl Iterator<Item = &(dyn std::error::Error + 'static)> {
+        self.source_error()
+            .into_iter()
+            .flat_map(anyhow::Error::chain)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use anyhow::Context;
+
+    use super::Error;
+
+    fn io_error() -> Error {
+        let result: Result<(), io::Error> =
+            Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        Error::Setup(result.context("could not read database").unwrap_err())
+    }
+
+    #[test]
+    fn root_cause() {
+        let error = io_error();
+        let root = error
+            .root_cause()
+            .downcast_ref::<io::Error>()
+            .expect("root cause should be an io::Error");
+        assert_eq!(root.kind(), io::ErrorKind::NotFound);
+
+        let error = Error::Provider(anyhow::anyhow!("simple"));
+        assert_eq!(error.root_cause().to_string(), "simple");
+
+        let error = Error::Multiple(vec![io_error(), Error::Provider(anyhow::anyhow!("second"))]);
+        assert!(error.root_cause().downcast_ref::<io::Error>().is_some());
+        assert_eq!(error.to_string(), "2 providers failed");
+    }
+
+    #[test]
+    fn causes() {
+        let error = io_error();
+        let causes: Vec<_> = error.causes().collect();
+        assert_eq!(causes.len(), 2);
+        assert_eq!(causes[0].to_string(), "could not read database");
+        assert!(causes[0].downcast_ref::<io::Error>().is_none());
+        assert!(causes[1].downcast_ref::<io::Error>().is_some());
+    }
+}
@@ -1,15 +1,30 @@
-use std::sync::Arc;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
 
-use crate::{domain::Location, error::Error, providers::Provider};
+use crate::{
+    domain::{set_global_provider, Location},
+    error::Error,
+    providers::{AsyncProviderFactory, CacheStats, Provider, ProviderDescription},
+};
 use anyhow::anyhow;
-use futures::{future::LocalBoxFuture, FutureExt};
+use futures::{
+    future::{join_all, LocalBoxFuture},
+    Future, FutureExt,
+};
+use ipnetwork::IpNetwork;
 use lazy_static::lazy_static;
+use rand::Rng;
 
 #[cfg(feature = "actix-web-v3")]
-use actix_web_3::{dev, web, FromRequest, HttpRequest};
+use actix_web_3::{dev, http::HeaderName, rt, web, FromRequest, HttpRequest};
 
 #[cfg(feature = "actix-web-v4")]
-use actix_web_4::{dev, web, FromRequest, HttpRequest};
+use actix_web_4::{dev, http::header::HeaderName, rt, web, FromRequest, HttpMessage, HttpRequest};
 
 impl FromRequest for Location {
     #[cfg(feature = "actix-web-v3")]
@@ -22,97 +37,416 @@ impl FromRequest for Location {
     fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
         let req = req.clone();
         async move {
-            let config = LocationConfig::from_req(&req).clone();
-            let mut result: Option<Result<Self, Self::Error>> = None;
-            for provider in config.providers {
-                if let Ok(Some(location)) = provider.get_location(&req).await {
-                    #[cfg(feature = "cadence")]
-                    {
-                        if let Some(metrics) = config.metrics.as_ref() {
-                            if provider.expect_city() && location.city.is_none() {
-                                metrics
-                                    .incr_with_tags("location.unknown.city")
-                                    .with_tag("provider", provider.name())
-                                    .try_send()
-                                    .ok();
-                            }
-                            if provider.expect_region() && location.region.is_none() {
-                                metrics
-                                    .incr_with_tags("location.unknown.region")
-                                    .with_tag("provider", provider.name())
-                                    .try_send()
-                                    .ok();
-                            }
-                            if provider.expect_country() && location.country.is_none() {
-                                metrics
-                                    .incr_with_tags("location.unknown.country")
-                                    .with_tag("provider", provider.name())
-                                    .try_send()
-                                    .ok();
-                            }
-                        }
-                    }
-
-                    result = Some(Ok(location));
+            // Reuse the location if it was already extracted for this request.
+            if let Some(location) = req.extensions().get::<Location>() {
+                return Ok(location.clone());
+            }
 
-                    break;
+            let result = locate(&req).await;
+            if let Ok(location) = &result {
+                #[cfg(feature = "tracing")]
+                if LocationConfig::from_req(&req).tracing_integration {
+                    record_on_current_span(location);
                 }
+                req.extensions_mut().insert(location.clone());
             }
+            result
+        }
+        .boxed_local()
+    }
+}
 
-            #[cfg(feature = "cadence")]
-            let metrics = config.metrics.as_ref();
-
-            result.unwrap_or_else(|| {
-                #[cfg(feature = "cadence")]
-                {
-                    if let Some(metrics) = metrics {
-                        metrics
-                            .incr_with_tags("location.unknown.city")
-                            .with_tag("provider", "none")
-                            .try_send()
-                            .ok();
-                        metrics
-                            .incr_with_tags("location.unknown.region")
-                            .with_tag("provider", "none")
-                            .try_send()
-                            .ok();
-                        metrics
-                            .incr_with_tags("location.unknown.country")
-                            .with_tag("provider", "none")
-                            .try_send()
-                            .ok();
-                    }
+/// Record the country, region and city of `location` on the current span, as
+/// the fields `location.country`, `location.region` and `location.city`.
+///
+/// Only fields declared when the span was created can be recorded, as the
+/// `MozLog` middleware from `tracing-actix-web-mozlog` does for its request
+/// span.
+#[cfg(feature = "tracing")]
+fn record_on_current_span(location: &Location) {
+    let span = tracing::Span::current();
+    if let Some(country) = location.country_ref() {
+        span.record("location.country", country);
+    }
+    if let Some(region) = location.region_ref() {
+        span.record("location.region", region);
+    }
+    if let Some(city) = location.city_ref() {
+        span.record("location.city", city);
+    }
+}
+
+/// Determine the location of the client that sent `req` using the request's
+/// [`LocationConfig`].
+async fn locate(req: &HttpRequest) -> Result<Location, Error> {
+    let config = LocationConfig::from_req(req).clone();
+
+    if config.is_blocked(req) {
+        return Location::build()
+            .provider("blocked".to_string())
+            .finish()
+            .map_err(|_| Error::Http(anyhow!("Bug when processing blocked result")));
+    }
+
+    if let Some(location) = config
+        .request_context_extractor
+        .as_ref()
+        .and_then(|extractor| extractor(req))
+    {
+        return Ok(location);
+    }
+
+    let mut result: Option<Result<Location, Error>> = None;
+    let mut errors = Vec::new();
+    let weighted = config.choose_weighted_providers(&mut rand::thread_rng());
+    let chain = config
+        .warm_start_providers
+        .iter()
+        .cloned()
+        .chain(weighted)
+        .chain(config.providers.iter().cloned());
+    for provider in chain {
+        let lookup = provider.get_location(req);
+        let lookup = match config.provider_timeouts.get(provider.name()) {
+            Some(limit) => rt::time::timeout(*limit, lookup).await.unwrap_or_else(|_| {
+                Err(Error::Provider(anyhow!(
+                    "{} timed out after {:?}",
+                    provider.name(),
+                    limit
+                )))
+            }),
+            None => lookup.await,
+        };
+        let location = match lookup {
+            Ok(Some(location)) => location,
+            Ok(None) => continue,
+            Err(error) => match config.provider_error_policy {
+                ProviderErrorPolicy::Swallow => continue,
+                ProviderErrorPolicy::PropagateFirst => return Err(error),
+                ProviderErrorPolicy::PropagateAll => {
+                    errors.push(error);
+                    continue;
                 }
+            },
+        };
 
-                Location::build()
-                    .provider("none".to_string())
-                    .finish()
-                    .map_err(|_| Error::Http(anyhow!("Bug when processing default result")))
-            })
+        #[cfg(feature = "cadence")]
+        {
+            if let Some(metrics) = config.metrics.as_ref() {
+                let metrics_name = provider.metrics_name();
+                if provider.expect_city() && location.city_ref().is_none() {
+                    metrics
+                        .incr_with_tags("location.unknown.city")
+                        .with_tag("provider", &metrics_name)
+                        .try_send()
+                        .ok();
+                }
+                if provider.expect_region() && location.region_ref().is_none() {
+                    metrics
+                        .incr_with_tags("location.unknown.region")
+                        .with_tag("provider", &metrics_name)
+                        .try_send()
+                        .ok();
+                }
+                if provider.expect_country() && location.country_ref().is_none() {
+                    metrics
+                        .incr_with_tags("location.unknown.country")
+                        .with_tag("provider", &metrics_name)
+                        .try_send()
+                        .ok();
+                }
+            }
         }
-        .boxed_local()
+
+        result = Some(Ok(location));
+
+        break;
+    }
+
+    // A location found after an error wins, so errors are only returned if
+    // no provider found one.
+    if result.is_none() && !errors.is_empty() {
+        return Err(Error::Multiple(errors));
     }
+
+    #[cfg(feature = "cadence")]
+    let metrics = config.metrics.as_ref();
+
+    result.unwrap_or_else(|| {
+        #[cfg(feature = "cadence")]
+        {
+            if let Some(metrics) = metrics {
+                metrics
+                    .incr_with_tags("location.unknown.city")
+                    .with_tag("provider", "none")
+                    .try_send()
+                    .ok();
+                metrics
+                    .incr_with_tags("location.unknown.region")
+                    .with_tag("provider", "none")
+                    .try_send()
+                    .ok();
+                metrics
+                    .incr_with_tags("location.unknown.country")
+                    .with_tag("provider", "none")
+                    .try_send()
+                    .ok();
+            }
+        }
+
+        Location::build()
+            .provider("none".to_string())
+            .finish()
+            .map_err(|_| Error::Http(anyhow!("Bug when processing default result")))
+    })
 }
 
 /// Configuration for how to determine location from a request.
+///
+/// The configuration is looked up from the request's app data, either directly
+/// or wrapped in [`web::Data`]. Since app data registered on a scope or
+/// resource takes precedence over app data registered on the app, a route can
+/// use a different configuration than the rest of the application:
+///
+/// ```ignore
+/// App::new()
+///     .app_data(LocationConfig::default().with_provider(default_provider))
+///     .service(
+///         web::scope("/premium")
+///             .app_data(LocationConfig::default().with_provider(premium_provider))
+///             .route("/", web::get().to(handler)),
+///     )
+/// ```
 #[derive(Clone, Default)]
 pub struct LocationConfig {
+    /// Providers that are consulted before any others, in order.
+    warm_start_providers: Vec<Arc<dyn Provider>>,
+
     /// The provider to request location information from.
-    providers: Vec<Arc<Box<dyn Provider>>>,
+    providers: Vec<Arc<dyn Provider>>,
+
+    /// Providers that are randomly chosen from, with their relative weights.
+    weighted_providers: Vec<(Arc<dyn Provider>, u32)>,
+
+    /// Client addresses that are never looked up.
+    ip_blocklist: Vec<IpNetwork>,
 
     /// An optional sink to send metrics to.
     #[cfg(feature = "cadence")]
     metrics: Option<Arc<dyn cadence::CountedExt + Send + Sync>>,
+
+    /// Whether to record extracted locations on the current tracing span.
+    #[cfg(feature = "tracing")]
+    tracing_integration: bool,
+
+    /// What to do when a provider fails.
+    provider_error_policy: ProviderErrorPolicy,
+
+    /// Time limits for individual providers, by provider name.
+    provider_timeouts: HashMap<String, Duration>,
+
+    /// A function that finds locations before any provider is consulted.
+    request_context_extractor: Option<RequestContextExtractor>,
+}
+
+/// A function that finds the location of a request from its context, set with
+/// [`LocationConfig::with_request_context_extractor`].
+type RequestContextExtractor = Arc<dyn Fn(&HttpRequest) -> Option<Location> + Send + Sync>;
+
+/// What to do when a provider returns an error, set with
+/// [`LocationConfig::with_provider_error_policy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProviderErrorPolicy {
+    /// Ignore the error and try the next provider. This is the default.
+    #[default]
+    Swallow,
+
+    /// Return the first error, without trying any more providers.
+    PropagateFirst,
+
+    /// Keep trying providers after an error. If a later provider finds a
+    /// location, it is returned and the errors are discarded. Otherwise, all
+    /// of the errors are returned as an [`Error::Multiple`] instead of an
+    /// unknown location.
+    PropagateAll,
+}
+
+impl fmt::Debug for LocationConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("LocationConfig");
+        debug
+            .field(
+                "warm_start_providers",
+                &self
+                    .warm_start_providers
+                    .iter()
+                    .map(|provider| provider.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "providers",
+                &self
+                    .providers
+                    .iter()
+                    .map(|provider| provider.name())
+                    .collect::<Vec<_>>(),
+            )
+            .field(
+                "weighted_providers",
+                &self
+                    .weighted_providers
+                    .iter()
+                    .map(|(provider, weight)| (provider.name(), weight))
+                    .collect::<Vec<_>>(),
+            )
+            .field("ip_blocklist", &self.ip_blocklist);
+        #[cfg(feature = "cadence")]
+        debug.field("metrics", &self.metrics.is_some());
+        #[cfg(feature = "tracing")]
+        debug.field("tracing_integration", &self.tracing_integration);
+        debug.field("provider_error_policy", &self.provider_error_policy);
+        debug.field("provider_timeouts", &self.provider_timeouts);
+        debug.field(
+            "request_context_extractor",
+            &self.request_context_extractor.is_some(),
+        );
+        debug.finish()
+    }
+}
+
+impl fmt::Display for LocationConfig {
+    /// A single line summary for startup logs, such as
+    /// `LocationConfig: providers=[maxmind (weight 3), fallback], timeouts=[maxmind 50ms], metrics=enabled`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LocationConfig: providers=[")?;
+        let mut separator = "";
+        for provider in &self.warm_start_providers {
+            write!(f, "{}{} (warm start)", separator, provider.name())?;
+            separator = ", ";
+        }
+        for (provider, weight) in &self.weighted_providers {
+            write!(f, "{}{} (weight {})", separator, provider.name(), weight)?;
+            separator = ", ";
+        }
+        for provider in &self.providers {
+            write!(f, "{}{}", separator, provider.name())?;
+            separator = ", ";
+        }
+        write!(f, "]")?;
+        if !self.ip_blocklist.is_empty() {
+            write!(f, ", blocked_ip_ranges={}", self.ip_blocklist.len())?;
+        }
+        if !self.provider_timeouts.is_empty() {
+            let mut timeouts: Vec<_> = self.provider_timeouts.iter().collect();
+            timeouts.sort();
+            write!(f, ", timeouts=[")?;
+            for (index, (name, limit)) in timeouts.into_iter().enumerate() {
+                let separator = if index == 0 { "" } else { ", " };
+                write!(f, "{}{} {:?}", separator, name, limit)?;
+            }
+            write!(f, "]")?;
+        }
+
+        #[cfg(feature = "cadence")]
+        let metrics_enabled = self.metrics.is_some();
+        #[cfg(not(feature = "cadence"))]
+        let metrics_enabled = false;
+        write!(
+            f,
+            ", metrics={}",
+            if metrics_enabled {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        )
+    }
+}
+
+/// A snapshot of the state of a [`LocationConfig`], for debugging endpoints.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LocationConfigSnapshot {
+    /// The number of providers, including weighted providers.
+    pub provider_count: usize,
+
+    /// A description of each provider, in the order they are consulted.
+    pub providers: Vec<ProviderDescription>,
+
+    /// The combined cache statistics of all providers that have a cache, or
+    /// `None` if no providers have a cache.
+    pub cache_stats: Option<CacheStats>,
+
+    /// The time limit for each provider's lookups in milliseconds, by
+    /// provider name, as set by
+    /// [`with_provider_timeout_map`](LocationConfig::with_provider_timeout_map).
+    pub timeouts_ms: BTreeMap<String, u64>,
 }
 
 lazy_static! {
     static ref DEFAULT_LOCATION_CONFIG: LocationConfig = LocationConfig::default();
+    static ref X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
 }
 
 impl LocationConfig {
-    /// Add a provider to this configuration. It will be wrapped into an `Arc<Box<T>>`.
+    /// Add a provider to this configuration. It will be wrapped into an
+    /// `Arc<T>`. A provider that is already boxed, such as a
+    /// `Box<dyn Provider>`, can also be added.
     pub fn with_provider<P: Provider + 'static>(mut self, provider: P) -> Self {
-        self.providers.push(Arc::new(Box::new(provider)));
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Add a provider that is consulted before all other providers on every
+    /// request, including weighted providers. This is intended for fast
+    /// lookups of pre-resolved locations, such as a [`CachingProvider`]
+    /// filled at startup. If it doesn't produce a location, the rest of the
+    /// providers are tried as usual.
+    ///
+    /// Multiple warm start providers are consulted in the order they were
+    /// added.
+    ///
+    /// [`CachingProvider`]: crate::providers::CachingProvider
+    pub fn with_warm_start_provider<P: Provider + 'static>(mut self, provider: P) -> Self {
+        self.warm_start_providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Add a provider to this configuration that is chosen randomly among the
+    /// other weighted providers, in proportion to `weight`.
+    ///
+    /// For each request, one weighted provider is chosen and consulted first.
+    /// If it doesn't produce a location, another of the remaining weighted
+    /// providers is chosen in the same way, until they have all been tried.
+    /// Then the providers added with [`with_provider`](Self::with_provider)
+    /// are tried in order. Providers with a weight of 0 are never chosen.
+    pub fn with_weighted_provider<P: Provider + 'static>(
+        mut self,
+        provider: P,
+        weight: u32,
+    ) -> Self {
+        self.weighted_providers.push((Arc::new(provider), weight));
+        self
+    }
+
+    /// Add a provider to this configuration that is constructed asynchronously
+    /// by `factory`.
+    pub async fn with_provider_factory<F: AsyncProviderFactory + 'static>(
+        mut self,
+        factory: F,
+    ) -> Result<Self, Error> {
+        self.providers.push(Arc::from(factory.build().await?));
+        Ok(self)
+    }
+
+    /// Deny lookups for clients whose address is in any of `ranges`.
+    ///
+    /// The client address is the first entry of the `X-Forwarded-For` header,
+    /// or the peer address if that header isn't present. Blocked requests are
+    /// not passed to any provider, and instead produce an empty location with
+    /// a provider of `"blocked"`.
+    pub fn with_ip_blocklist(mut self, ranges: Vec<IpNetwork>) -> Self {
+        self.ip_blocklist.extend(ranges);
         self
     }
 
@@ -126,6 +460,179 @@ impl LocationConfig {
         self
     }
 
+    /// When enabled, the country, region and city of extracted locations are
+    /// recorded on the current tracing span as `location.country`,
+    /// `location.region` and `location.city`.
+    ///
+    /// The `MozLog` middleware from `tracing-actix-web-mozlog` declares these
+    /// fields on its request span, so they appear in `request.summary` events.
+    #[cfg(feature = "tracing")]
+    pub fn with_tracing_integration(mut self, enabled: bool) -> Self {
+        self.tracing_integration = enabled;
+        self
+    }
+
+    /// Choose what happens when a provider returns an error. By default,
+    /// errors are ignored and the next provider is tried, which can hide
+    /// problems such as a missing database.
+    pub fn with_provider_error_policy(mut self, policy: ProviderErrorPolicy) -> Self {
+        self.provider_error_policy = policy;
+        self
+    }
+
+    /// Limit how long each provider may take to look up a location, keyed by
+    /// [provider name](Provider::name), so that a fast in-memory provider and
+    /// a slow HTTP provider in the same chain can have different limits.
+    ///
+    /// A provider that takes too long fails with [`Error::Provider`], which
+    /// is handled according to the
+    /// [provider error policy](Self::with_provider_error_policy). Providers
+    /// that aren't in `map` have no time limit.
+    pub fn with_provider_timeout_map(mut self, map: HashMap<String, Duration>) -> Self {
+        self.provider_timeouts = map;
+        self
+    }
+
+    /// Find locations with `extractor` before consulting any provider, such
+    /// as from a session cookie and a cache of session locations. If it
+    /// returns a location, that location is used without contacting any
+    /// provider. Otherwise the providers are consulted as usual.
+    ///
+    /// The extractor is synchronous and called for every request that isn't
+    /// in the IP blocklist, so it should be fast.
+    pub fn with_request_context_extractor<F>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Option<Location> + Send + Sync + 'static,
+    {
+        self.request_context_extractor = Some(Arc::new(extractor));
+        self
+    }
+
+    /// Install the first provider of this configuration, in the order of
+    /// [`providers`](Self::providers), as the global provider used by
+    /// [`Location::from_ip`]. See [`Location::set_global_provider`] for the
+    /// limitations of the global provider.
+    ///
+    /// Returns an [`Error::Setup`] if this configuration has no providers,
+    /// or if the global provider was already set.
+    pub fn as_global_provider(&self) -> Result<(), Error> {
+        let provider = self
+            .all_providers()
+            .next()
+            .ok_or_else(|| Error::Setup(anyhow!("there are no providers to use globally")))?;
+        set_global_provider(provider.clone())
+    }
+
+    /// The names of the providers in this configuration, in the order they
+    /// are consulted.
+    ///
+    /// Warm start providers are listed first, then weighted providers,
+    /// although only one of them is consulted for each request, followed by
+    /// the other providers in priority order.
+    pub fn providers(&self) -> impl Iterator<Item = &str> {
+        self.all_providers().map(|provider| provider.name())
+    }
+
+    /// Describe each of the providers in this configuration, in the same
+    /// order as [`providers`](Self::providers). This is intended for health
+    /// check and diagnostic endpoints.
+    pub fn describe_providers(&self) -> Vec<ProviderDescription> {
+        self.all_providers()
+            .map(|provider| provider.describe())
+            .collect()
+    }
+
+    /// Call [`Provider::warm_up`] on all of the providers in this
+    /// configuration concurrently, such as at server startup. The result of
+    /// each is returned along with the provider's name, in the same order as
+    /// [`providers`](Self::providers).
+    pub fn warm_up_all(&self) -> impl Future<Output = Vec<(&str, Result<(), Error>)>> {
+        join_all(
+            self.all_providers()
+                .map(|provider| async move { (provider.name(), provider.warm_up().await) }),
+        )
+    }
+
+    /// Take a snapshot of the current state of this configuration and its
+    /// providers, such as for a debugging endpoint.
+    pub fn inspect(&self) -> LocationConfigSnapshot {
+        let cache_stats = self
+            .all_providers()
+            .filter_map(|provider| provider.cache_stats())
+            .reduce(|total, stats| CacheStats {
+                hits: total.hits + stats.hits,
+                misses: total.misses + stats.misses,
+                evictions: total.evictions + stats.evictions,
+            });
+
+        LocationConfigSnapshot {
+            provider_count: self.provider_count(),
+            providers: self.describe_providers(),
+            cache_stats,
+            timeouts_ms: self
+                .provider_timeouts
+                .iter()
+                .map(|(name, limit)| (name.clone(), limit.as_millis() as u64))
+                .collect(),
+        }
+    }
+
+    /// The number of providers in this configuration, including warm start
+    /// and weighted providers.
+    pub fn provider_count(&self) -> usize {
+        self.warm_start_providers.len() + self.weighted_providers.len() + self.providers.len()
+    }
+
+    /// All of the providers in this configuration, in the order of
+    /// [`providers`](Self::providers).
+    fn all_providers(&self) -> impl Iterator<Item = &Arc<dyn Provider>> {
+        self.warm_start_providers
+            .iter()
+            .chain(self.weighted_providers.iter().map(|(provider, _)| provider))
+            .chain(self.providers.iter())
+    }
+
+    /// Randomly order the weighted providers, choosing each in proportion to
+    /// its weight among the providers that haven't been chosen yet. Providers
+    /// with a weight of 0 are left out.
+    fn choose_weighted_providers<R: Rng>(&self, rng: &mut R) -> Vec<Arc<dyn Provider>> {
+        let mut remaining: Vec<_> = self
+            .weighted_providers
+            .iter()
+            .filter(|(_, weight)| *weight > 0)
+            .collect();
+        let mut chosen = Vec::with_capacity(remaining.len());
+
+        while !remaining.is_empty() {
+            let total: u64 = remaining.iter().map(|(_, weight)| u64::from(*weight)).sum();
+            let mut draw = rng.gen_range(0..total);
+            let index = remaining
+                .iter()
+                .position(|(_, weight)| {
+                    let weight = u64::from(*weight);
+                    if draw < weight {
+                        true
+                    } else {
+                        draw -= weight;
+                        false
+                    }
+                })
+                .expect("the draw is less than the total weight");
+            chosen.push(remaining.remove(index).0.clone());
+        }
+        chosen
+    }
+
+    /// Is the client that sent `req` in the IP blocklist?
+    fn is_blocked(&self, req: &HttpRequest) -> bool {
+        if self.ip_blocklist.is_empty() {
+            return false;
+        }
+        client_ip(req)
+            .map(|ip| self.ip_blocklist.iter().any(|range| range.contains(ip)))
+            .unwrap_or(false)
+    }
+
     fn from_req(req: &HttpRequest) -> &Self {
         req.app_data::<Self>()
             .or_else(|| req.app_data::<web::Data<Self>>().map(|d| d.as_ref()))
@@ -133,9 +640,66 @@ impl LocationConfig {
     }
 }
 
+/// Determine the client's address from the first entry of the
+/// `X-Forwarded-For` header, falling back to the peer address if the header is
+/// missing or invalid.
+pub(crate) fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    forwarded_addr(req, &[])
+        .ok()
+        .flatten()
+        .or_else(|| req.peer_addr().map(|addr| addr.ip()))
+}
+
+/// The client address from the `X-Forwarded-For` header of `request`, or
+/// from the first of `additional_headers` that is present, if there is
+/// one. Some proxies send the header multiple times, so all instances of
+/// it are read as one list.
+pub(crate) fn forwarded_addr(
+    request: &HttpRequest,
+    additional_headers: &[HeaderName],
+) -> Result<Option<IpAddr>, Error> {
+    let header = match std::iter::once(&*X_FORWARDED_FOR)
+        .chain(additional_headers)
+        .find(|name| request.headers().contains_key(*name))
+    {
+        Some(name) => request
+            .headers()
+            .get_all(name)
+            .map(|value| value.to_str())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| Error::Http(e.into()))?
+            .join(","),
+        None => return Ok(None),
+    };
+
+    // Expect a typical X-Forwarded-For where the first address is
+    // the client's, the front ends should ensure this
+    let value = header.split(',').next().unwrap_or_default().trim();
+    value
+        .parse::<IpAddr>()
+        // Fallback to parsing as SocketAddr for when a port
+        // number's included
+        .or_else(|_| value.parse::<SocketAddr>().map(|socket| socket.ip()))
+        .map(Some)
+        .map_err(|e| Error::Http(e.into()))
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{providers::FallbackProvider, Location, LocationConfig};
+    use std::{
+        collections::{BTreeMap, HashMap},
+        sync::{atomic::Ordering, Arc},
+        time::Duration,
+    };
+
+    use super::{LocationConfigSnapshot, ProviderErrorPolicy};
+    use crate::{
+        providers::{
+            testing::{CountingProvider, EmptyProvider, FailingProvider},
+            AsyncProviderFactory, CacheStats, CircuitBreakerProvider, FallbackProvider,
+        },
+        Error, Location, LocationConfig, Provider,
+    };
 
     #[cfg(not(feature = "actix-web-v4"))]
     use actix_web_3::{dev::Payload, test::TestRequest, FromRequest};
@@ -143,6 +707,7 @@ mod tests {
     use actix_web_4::{dev::Payload, test::TestRequest, FromRequest};
 
     #[actix_rt::test]
+    #[allow(deprecated)]
     async fn default_config() {
         let req = TestRequest::default()
             .app_data(LocationConfig::default())
@@ -155,14 +720,23 @@ mod tests {
             Location {
                 country: None,
                 region: None,
+                region_name: None,
                 city: None,
                 dma: None,
+                market_area_code: None,
+                latitude: None,
+                longitude: None,
+                accuracy_radius: None,
+                timezone: None,
+                isp: None,
+                connection_type: None,
                 provider: "none".to_string()
             }
         );
     }
 
     #[actix_rt::test]
+    #[allow(deprecated)]
     async fn with_provider() {
         let provider = FallbackProvider::new(
             Location::build()
@@ -180,12 +754,801 @@ mod tests {
             Location {
                 country: Some("CA".to_string()),
                 region: Some("ON".to_string()),
+                region_name: None,
                 city: Some("Toronto".to_string()),
                 dma: None,
+                market_area_code: None,
+                latitude: None,
+                longitude: None,
+                accuracy_radius: None,
+                timezone: None,
+                isp: None,
+                connection_type: None,
                 provider: "fallback".to_string()
             }
         );
     }
 
+    #[actix_rt::test]
+    async fn location_is_cached_per_request() {
+        let provider = CountingProvider::default();
+        let config = LocationConfig::default().with_provider(provider.clone());
+
+        let req = TestRequest::default()
+            .app_data(config.clone())
+            .to_http_request();
+        let first = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        let second = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(first, second);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+
+        let req = TestRequest::default().app_data(config).to_http_request();
+        Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(
+            provider.calls.load(Ordering::SeqCst),
+            2,
+            "each request should do its own lookup"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn with_boxed_provider() {
+        let provider: Box<dyn Provider> = Box::new(FallbackProvider::new(
+            Location::build().country("CA".to_string()),
+        ));
+        let config = LocationConfig::default().with_provider(provider);
+        assert_eq!(config.providers().collect::<Vec<_>>(), vec!["fallback"]);
+
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.country(), "CA");
+    }
+
+    #[cfg(feature = "actix-web-v4")]
+    #[actix_rt::test]
+    async fn scoped_config_overrides_app_config() {
+        use actix_web_4::{test, web, App};
+
+        async fn handler(location: Location) -> String {
+            location.country()
+        }
+
+        let global_config = LocationConfig::default().with_provider(FallbackProvider::new(
+            Location::build().country("CA".to_string()),
+        ));
+        let premium_config = LocationConfig::default().with_provider(FallbackProvider::new(
+            Location::build().country("MX".to_string()),
+        ));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(global_config)
+                .route("/", web::get().to(handler))
+                .service(
+                    web::scope("/premium")
+                        .app_data(premium_config)
+                        .route("/", web::get().to(handler)),
+                ),
+        )
+        .await;
+
+        let req = test::TestRequest::with_uri("/").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "CA");
+
+        let req = test::TestRequest::with_uri("/premium/").to_request();
+        assert_eq!(test::call_and_read_body(&app, req).await, "MX");
+    }
+
+    #[actix_rt::test]
+    async fn with_provider_factory() {
+        struct TestFactory;
+
+        #[async_trait::async_trait(?Send)]
+        impl AsyncProviderFactory for TestFactory {
+            async fn build(&self) -> Result<Box<dyn Provider>, Error> {
+                Ok(Box::new(FallbackProvider::new(
+                    Location::build().country("CA".to_string()),
+                )))
+            }
+        }
+
+        let config = LocationConfig::default()
+            .with_provider_factory(TestFactory)
+            .await
+            .expect("could not build provider");
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.country(), "CA");
+    }
+
+    #[test]
+    fn providers() {
+        struct NamedProvider;
+
+        #[async_trait::async_trait(?Send)]
+        impl Provider for NamedProvider {
+            fn name(&self) -> &str {
+                "named"
+            }
+
+            async fn get_location(
+                &self,
+                _request: &super::HttpRequest,
+            ) -> Result<Option<Location>, Error> {
+                Ok(None)
+            }
+        }
+
+        assert_eq!(LocationConfig::default().provider_count(), 0);
+
+        let config = LocationConfig::default()
+            .with_provider(NamedProvider)
+            .with_provider(FallbackProvider::new(Location::build()));
+        assert_eq!(
+            config.providers().collect::<Vec<_>>(),
+            vec!["named", "fallback"]
+        );
+        assert_eq!(config.provider_count(), 2);
+
+        let descriptions = config.describe_providers();
+        assert_eq!(descriptions.len(), 2);
+        assert_eq!(descriptions[0].name, "named");
+        assert_eq!(descriptions[0].kind, "custom");
+        assert_eq!(descriptions[1].name, "fallback");
+        assert_eq!(descriptions[1].kind, "fallback");
+    }
+
+    #[test]
+    fn debug_and_display() {
+        let config = LocationConfig::default()
+            .with_provider(CircuitBreakerProvider::new(
+                FallbackProvider::new(Location::build()),
+                3,
+                Duration::from_secs(30),
+            ))
+            .with_weighted_provider(FallbackProvider::new(Location::build()), 3)
+            .with_ip_blocklist(vec!["192.0.2.0/24".parse().unwrap()])
+            .with_provider_timeout_map(HashMap::from([
+                ("fallback".to_string(), Duration::from_millis(500)),
+                ("maxmind".to_string(), Duration::from_millis(50)),
+            ]));
+
+        let debug = format!("{:?}", config);
+        assert!(
+            debug.starts_with(
+                r#"LocationConfig { warm_start_providers: [], providers: ["fallback"], weighted_providers: [("fallback", 3)], ip_blocklist: [V4(Ipv4Network { addr: 192.0.2.0, prefix: 24 })]"#
+            ),
+            "{}",
+            debug
+        );
+        assert!(!debug.contains("Arc"), "{}", debug);
+
+        assert_eq!(
+            config.to_string(),
+            "LocationConfig: providers=[fallback (weight 3), fallback], blocked_ip_ranges=1, timeouts=[fallback 500ms, maxmind 50ms], metrics=disabled"
+        );
+        assert_eq!(
+            LocationConfig::default().to_string(),
+            "LocationConfig: providers=[], metrics=disabled"
+        );
+    }
+
+    #[cfg(feature = "cadence")]
+    #[test]
+    fn display_with_metrics() {
+        let config = LocationConfig::default()
+            .with_provider(FallbackProvider::new(Location::build()).with_name("maxmind"))
+            .with_provider(FallbackProvider::new(Location::build()))
+            .with_metrics(Arc::new(cadence::StatsdClient::from_sink(
+                "test",
+                cadence::NopMetricSink,
+            )));
+
+        let display = config.to_string();
+        assert!(
+            display.contains("providers=[maxmind, fallback]"),
+            "{}",
+            display
+        );
+        assert!(display.contains("metrics=enabled"), "{}", display);
+    }
+
+    #[test]
+    fn inspect() {
+        struct CachedProvider(CacheStats);
+
+        #[async_trait::async_trait(?Send)]
+        impl Provider for CachedProvider {
+            fn name(&self) -> &str {
+                "cached"
+            }
+
+            async fn get_location(
+                &self,
+                _request: &super::HttpRequest,
+            ) -> Result<Option<Location>, Error> {
+                Ok(None)
+            }
+
+            fn cache_stats(&self) -> Option<CacheStats> {
+                Some(self.0)
+            }
+        }
+
+        let snapshot = LocationConfig::default().inspect();
+        assert_eq!(snapshot.provider_count, 0);
+        assert_eq!(snapshot.cache_stats, None);
+        assert!(snapshot.timeouts_ms.is_empty());
+
+        let config = LocationConfig::default()
+            .with_weighted_provider(
+                CachedProvider(CacheStats {
+                    hits: 10,
+                    misses: 2,
+                    evictions: 1,
+                }),
+                1,
+            )
+            .with_provider(CircuitBreakerProvider::new(
+                CachedProvider(CacheStats {
+                    hits: 5,
+                    misses: 3,
+                    evictions: 0,
+                }),
+                3,
+                Duration::from_secs(30),
+            ))
+            .with_provider(FallbackProvider::new(Location::build()))
+            .with_provider_timeout_map(HashMap::from([(
+                "cached".to_string(),
+                Duration::from_millis(250),
+            )]));
+
+        let snapshot = config.inspect();
+        assert_eq!(
+            snapshot,
+            LocationConfigSnapshot {
+                provider_count: 3,
+                providers: config.describe_providers(),
+                cache_stats: Some(CacheStats {
+                    hits: 15,
+                    misses: 5,
+                    evictions: 1,
+                }),
+                timeouts_ms: BTreeMap::from([("cached".to_string(), 250)]),
+            }
+        );
+        let kinds: Vec<_> = snapshot.providers.iter().map(|p| p.kind).collect();
+        assert_eq!(kinds, vec!["custom", "custom", "fallback"]);
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&snapshot).expect("could not serialize");
+            assert_eq!(json["provider_count"], 3);
+            assert_eq!(json["cache_stats"]["hits"], 15);
+            assert_eq!(json["providers"][2]["kind"], "fallback");
+            assert_eq!(json["timeouts_ms"]["cached"], 250);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn warm_up_all() {
+        struct ColdProvider {
+            fail: bool,
+        }
+
+        #[async_trait::async_trait(?Send)]
+        impl Provider for ColdProvider {
+            fn name(&self) -> &str {
+                if self.fail {
+                    "broken"
+                } else {
+                    "cold"
+                }
+            }
+
+            async fn get_location(
+                &self,
+                _request: &super::HttpRequest,
+            ) -> Result<Option<Location>, Error> {
+                Ok(None)
+            }
+
+            async fn warm_up(&self) -> Result<(), Error> {
+                if self.fail {
+                    Err(Error::Setup(anyhow::anyhow!("no seed file")))
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        let config = LocationConfig::default()
+            .with_provider(ColdProvider { fail: false })
+            .with_provider(FallbackProvider::new(Location::build()))
+            .with_weighted_provider(ColdProvider { fail: true }, 1);
+
+        let results = config.warm_up_all().await;
+        let names: Vec<_> = results.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["broken", "cold", "fallback"]);
+        assert!(matches!(results[0].1, Err(Error::Setup(_))));
+        assert!(results[1].1.is_ok());
+        assert!(results[2].1.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn ip_blocklist() {
+        let config = LocationConfig::default()
+            .with_provider(FallbackProvider::new(
+                Location::build().country("CA".to_string()),
+            ))
+            .with_ip_blocklist(vec![
+                "192.0.2.0/24".parse().unwrap(),
+                "2001:db8::/32".parse().unwrap(),
+            ]);
+
+        for (addr, expected_provider) in [
+            ("192.0.2.17", "blocked"),
+            ("192.0.2.17:4321, 10.0.0.1", "blocked"),
+            ("2001:db8::1", "blocked"),
+            ("198.51.100.4", "fallback"),
+            ("10.0.0.1, 192.0.2.17", "fallback"),
+        ] {
+            #[cfg(not(feature = "actix-web-v4"))]
+            let req = TestRequest::default()
+                .app_data(config.clone())
+                .header("X-Forwarded-For", addr)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let req = TestRequest::default()
+                .app_data(config.clone())
+                .insert_header(("X-Forwarded-For", addr))
+                .to_http_request();
+            let location = Location::from_request(&req, &mut Payload::None)
+                .await
+                .expect("error getting request");
+            assert_eq!(location.provider(), expected_provider, "for {}", addr);
+        }
+
+        let req = TestRequest::default()
+            .app_data(config.clone())
+            .peer_addr("192.0.2.1:80".parse().unwrap())
+            .to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(
+            location,
+            Location::build()
+                .provider("blocked".to_string())
+                .finish()
+                .unwrap()
+        );
+    }
+
+    #[cfg(feature = "actix-web-v4")]
+    #[test]
+    fn client_ip_reads_every_forwarded_header() {
+        use super::{client_ip, forwarded_addr};
+
+        for (headers, expected) in [
+            (vec!["192.0.2.17", "10.0.0.1"], "192.0.2.17"),
+            (vec!["192.0.2.17:4321, 10.0.0.1", "10.0.0.2"], "192.0.2.17"),
+            // The empty first instance makes the joined list start with an
+            // empty entry, so neither finds a forwarded address.
+            (vec!["", "192.0.2.17"], "198.51.100.1"),
+        ] {
+            let mut req = TestRequest::default().peer_addr("198.51.100.1:80".parse().unwrap());
+            for header in &headers {
+                req = req.append_header(("X-Forwarded-For", *header));
+            }
+            let req = req.to_http_request();
+
+            let ip = client_ip(&req).map(|ip| ip.to_string());
+            assert_eq!(ip.as_deref(), Some(expected), "for {:?}", headers);
+            let forwarded = forwarded_addr(&req, &[]).ok().flatten();
+            assert_eq!(
+                forwarded.is_some(),
+                expected != "198.51.100.1",
+                "for {:?}",
+                headers
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_provider_distribution() {
+        let config = LocationConfig::default()
+            .with_weighted_provider(
+                FallbackProvider::new(Location::build().country("CA".to_string())),
+                1,
+            )
+            .with_weighted_provider(
+                FallbackProvider::new(Location::build().country("MX".to_string())),
+                3,
+            )
+            .with_weighted_provider(
+                FallbackProvider::new(Location::build().country("US".to_string())),
+                0,
+            );
+
+        let mut rng = rand::thread_rng();
+        let mut counts = HashMap::new();
+        let draws = 10_000;
+        for _ in 0..draws {
+            let chosen = config.choose_weighted_providers(&mut rng);
+            assert_eq!(chosen.len(), 2, "each provider should be chosen once");
+            let provider = &chosen[0];
+            let request = TestRequest::default().to_http_request();
+            let location = futures::executor::block_on(provider.get_location(&request))
+                .expect("could not get location")
+                .expect("location was none");
+            *counts.entry(location.country()).or_insert(0) += 1;
+        }
+
+        assert_eq!(counts.get("US"), None, "zero weight should never be chosen");
+        let ca = f64::from(counts["CA"]) / f64::from(draws);
+        let mx = f64::from(counts["MX"]) / f64::from(draws);
+        assert!((ca - 0.25).abs() < 0.05, "CA chosen {} of the time", ca);
+        assert!((mx - 0.75).abs() < 0.05, "MX chosen {} of the time", mx);
+    }
+
+    #[test]
+    fn weighted_provider_all_zero() {
+        let config = LocationConfig::default().with_weighted_provider(
+            FallbackProvider::new(Location::build().country("CA".to_string())),
+            0,
+        );
+        assert!(config
+            .choose_weighted_providers(&mut rand::thread_rng())
+            .is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn weighted_providers_are_all_tried() {
+        // The empty provider is almost always chosen first, but the other
+        // weighted provider should still be tried before the fallback.
+        let config = LocationConfig::default()
+            .with_weighted_provider(EmptyProvider, 1_000)
+            .with_weighted_provider(
+                FallbackProvider::new(Location::build().country("CA".to_string())),
+                1,
+            )
+            .with_provider(FallbackProvider::new(
+                Location::build().country("MX".to_string()),
+            ));
+        for _ in 0..10 {
+            let req = TestRequest::default()
+                .app_data(config.clone())
+                .to_http_request();
+            let location = Location::from_request(&req, &mut Payload::None)
+                .await
+                .expect("error getting request");
+            assert_eq!(location.country(), "CA");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn weighted_provider_falls_through() {
+        let config = LocationConfig::default()
+            .with_weighted_provider(
+                FallbackProvider::new(Location::build().country("CA".to_string())),
+                0,
+            )
+            .with_provider(FallbackProvider::new(
+                Location::build().country("MX".to_string()),
+            ));
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.country(), "MX");
+    }
+
+    #[cfg(all(feature = "tracing", feature = "actix-web-v4"))]
+    #[actix_rt::test]
+    async fn tracing_integration() {
+        use actix_web_4::{test, web, App, HttpResponse};
+        use std::{io::Write, sync::Mutex};
+        use tracing_actix_web_mozlog::{JsonStorageLayer, MozLog, MozLogFormatLayer};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        #[derive(Clone, Default)]
+        struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = SharedBuffer::default();
+        let writer = buffer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(JsonStorageLayer)
+            .with(MozLogFormatLayer::new("test", move || writer.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let config = LocationConfig::default()
+            .with_provider(FallbackProvider::new(
+                Location::build()
+                    .country("CA".to_string())
+                    .region("ON".to_string()),
+            ))
+            .with_tracing_integration(true);
+        let app = test::init_service(App::new().wrap(MozLog::default()).app_data(config).route(
+            "/",
+            web::get().to(|_location: Location| async { HttpResponse::Ok().finish() }),
+        ))
+        .await;
+        test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let summary: serde_json::Value = output
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("invalid log line"))
+            .find(|event: &serde_json::Value| event["type"] == "request.summary")
+            .expect("no request.summary event");
+        assert_eq!(summary["Fields"]["location.country"], "CA");
+        assert_eq!(summary["Fields"]["location.region"], "ON");
+        assert_eq!(summary["Fields"].get("location.city"), None);
+    }
+
+    #[actix_rt::test]
+    async fn warm_start_provider_is_consulted_first() {
+        let config = LocationConfig::default()
+            .with_provider(FallbackProvider::new(
+                Location::build().country("MX".to_string()),
+            ))
+            .with_weighted_provider(
+                FallbackProvider::new(Location::build().country("US".to_string())),
+                1,
+            )
+            .with_warm_start_provider(
+                FallbackProvider::new(Location::build().country("CA".to_string()))
+                    .with_name("warm"),
+            );
+        assert_eq!(
+            config.providers().collect::<Vec<_>>(),
+            vec!["warm", "fallback", "fallback"]
+        );
+        assert_eq!(config.provider_count(), 3);
+
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.country(), "CA");
+        assert_eq!(location.provider(), "warm");
+    }
+
+    fn error_policy_config(policy: ProviderErrorPolicy) -> LocationConfig {
+        LocationConfig::default()
+            .with_provider(FailingProvider("first"))
+            .with_provider(FailingProvider("second"))
+            .with_provider(FallbackProvider::new(
+                Location::build().country("CA".to_string()),
+            ))
+            .with_provider_error_policy(policy)
+    }
+
+    #[actix_rt::test]
+    async fn provider_error_policy_swallow() {
+        let config = error_policy_config(ProviderErrorPolicy::Swallow);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("errors should be swallowed");
+        assert_eq!(location.country(), "CA");
+    }
+
+    #[actix_rt::test]
+    async fn provider_error_policy_propagate_first() {
+        let config = error_policy_config(ProviderErrorPolicy::PropagateFirst);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let error = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect_err("the first error should be returned");
+        assert!(matches!(error, Error::Provider(_)));
+        assert_eq!(error.root_cause().to_string(), "first is down");
+    }
+
+    #[actix_rt::test]
+    async fn provider_error_policy_propagate_all() {
+        // Errors are only returned when no provider finds a location.
+        let config = LocationConfig::default()
+            .with_provider(FailingProvider("first"))
+            .with_provider(FailingProvider("second"))
+            .with_provider_error_policy(ProviderErrorPolicy::PropagateAll);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let error = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect_err("all errors should be returned");
+        match error {
+            Error::Multiple(errors) => {
+                let messages: Vec<_> = errors
+                    .iter()
+                    .map(|error| error.root_cause().to_string())
+                    .collect();
+                assert_eq!(messages, vec!["first is down", "second is down"]);
+            }
+            error => panic!("expected Error::Multiple, got {:?}", error),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn provider_error_policy_propagate_all_after_success() {
+        // A location found after an error is returned instead of the error.
+        let config = LocationConfig::default()
+            .with_provider(FailingProvider("first"))
+            .with_provider(FallbackProvider::new(
+                Location::build().country("CA".to_string()),
+            ))
+            .with_provider_error_policy(ProviderErrorPolicy::PropagateAll);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("the location should be returned");
+        assert_eq!(location.country(), "CA");
+    }
+
+    #[actix_rt::test]
+    async fn provider_error_policy_without_errors() {
+        let config = LocationConfig::default()
+            .with_provider(FallbackProvider::new(
+                Location::build().country("CA".to_string()),
+            ))
+            .with_provider_error_policy(ProviderErrorPolicy::PropagateAll);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.country(), "CA");
+    }
+
+    /// A provider that takes `delay` to find a location in `country`.
+    // actix-web 3 uses the timers of an older Tokio, which don't run on the
+    // test runtime.
+    #[cfg(feature = "actix-web-v4")]
+    struct SlowProvider {
+        name: &'static str,
+        delay: Duration,
+        country: &'static str,
+    }
+
+    #[cfg(feature = "actix-web-v4")]
+    #[async_trait::async_trait(?Send)]
+    impl Provider for SlowProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        async fn get_location(
+            &self,
+            _request: &super::HttpRequest,
+        ) -> Result<Option<Location>, Error> {
+            actix_rt::time::sleep(self.delay).await;
+            Ok(Some(
+                Location::build()
+                    .country(self.country.to_string())
+                    .provider(self.name.to_string())
+                    .finish()
+                    .unwrap(),
+            ))
+        }
+    }
+
+    #[cfg(feature = "actix-web-v4")]
+    fn provider_timeout_config(policy: ProviderErrorPolicy) -> LocationConfig {
+        LocationConfig::default()
+            .with_provider(SlowProvider {
+                name: "cache",
+                delay: Duration::from_millis(50),
+                country: "US",
+            })
+            .with_provider(SlowProvider {
+                name: "http",
+                delay: Duration::from_millis(10),
+                country: "CA",
+            })
+            .with_provider_timeout_map(HashMap::from([
+                ("cache".to_string(), Duration::from_millis(1)),
+                ("http".to_string(), Duration::from_millis(500)),
+            ]))
+            .with_provider_error_policy(policy)
+    }
+
+    #[cfg(feature = "actix-web-v4")]
+    #[actix_rt::test]
+    async fn provider_timeouts() {
+        let config = provider_timeout_config(ProviderErrorPolicy::Swallow);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("the slow provider should be skipped");
+        assert_eq!(location.provider(), "http");
+        assert_eq!(location.country(), "CA");
+
+        let config = provider_timeout_config(ProviderErrorPolicy::PropagateFirst);
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let error = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect_err("the timeout should be returned");
+        assert!(matches!(error, Error::Provider(_)));
+        assert_eq!(error.root_cause().to_string(), "cache timed out after 1ms");
+    }
+
+    #[cfg(feature = "actix-web-v4")]
+    #[actix_rt::test]
+    async fn providers_without_timeouts() {
+        let config = provider_timeout_config(ProviderErrorPolicy::Swallow)
+            .with_provider_timeout_map(HashMap::new());
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.provider(), "cache");
+    }
+
+    #[actix_rt::test]
+    async fn request_context_extractor() {
+        let provider = CountingProvider::default();
+        let config = LocationConfig::default()
+            .with_provider(provider.clone())
+            .with_request_context_extractor(|req| {
+                let session = req.headers().get("X-Session")?.to_str().ok()?;
+                (session == "known").then(|| {
+                    Location::build()
+                        .country("MX".to_string())
+                        .provider("session".to_string())
+                        .finish()
+                        .unwrap()
+                })
+            });
+
+        #[cfg(not(feature = "actix-web-v4"))]
+        let req = TestRequest::default()
+            .header("X-Session", "known")
+            .app_data(config.clone())
+            .to_http_request();
+        #[cfg(feature = "actix-web-v4")]
+        let req = TestRequest::default()
+            .insert_header(("X-Session", "known"))
+            .app_data(config.clone())
+            .to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.provider(), "session");
+        assert_eq!(location.country(), "MX");
+        assert_eq!(
+            provider.calls.load(Ordering::SeqCst),
+            0,
+            "providers should not be consulted"
+        );
+
+        let req = TestRequest::default().app_data(config).to_http_request();
+        let location = Location::from_request(&req, &mut Payload::None)
+            .await
+            .expect("error getting request");
+        assert_eq!(location.provider(), "counting");
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+    }
+
     // TODO test metrics
 }
@@ -0,0 +1,471 @@
+//! Embedded tables of ISO 3166 codes.
+
+use phf::{phf_map, phf_set};
+
+/// English names of countries, keyed by their ISO 3166-1 alpha-2 code.
+pub(crate) static COUNTRY_NAMES: phf::Map<&'static str, &'static str> = phf_map! {
+    "AD" => "Andorra",
+    "AE" => "United Arab Emirates",
+    "AF" => "Afghanistan",
+    "AG" => "Antigua and Barbuda",
+    "AI" => "Anguilla",
+    "AL" => "Albania",
+    "AM" => "Armenia",
+    "AO" => "Angola",
+    "AQ" => "Antarctica",
+    "AR" => "Argentina",
+    "AS" => "American Samoa",
+    "AT" => "Austria",
+    "AU" => "Australia",
+    "AW" => "Aruba",
+    "AX" => "Åland Islands",
+    "AZ" => "Azerbaijan",
+    "BA" => "Bosnia and Herzegovina",
+    "BB" => "Barbados",
+    "BD" => "Bangladesh",
+    "BE" => "Belgium",
+    "BF" => "Burkina Faso",
+    "BG" => "Bulgaria",
+    "BH" => "Bahrain",
+    "BI" => "Burundi",
+    "BJ" => "Benin",
+    "BL" => "Saint Barthélemy",
+    "BM" => "Bermuda",
+    "BN" => "Brunei",
+    "BO" => "Bolivia",
+    "BQ" => "Caribbean Netherlands",
+    "BR" => "Brazil",
+    "BS" => "Bahamas",
+    "BT" => "Bhutan",
+    "BV" => "Bouvet Island",
+    "BW" => "Botswana",
+    "BY" => "Belarus",
+    "BZ" => "Belize",
+    "CA" => "Canada",
+    "CC" => "Cocos (Keeling) Islands",
+    "CD" => "Democratic Republic of the Congo",
+    "CF" => "Central African Republic",
+    "CG" => "Republic of the Congo",
+    "CH" => "Switzerland",
+    "CI" => "Côte d'Ivoire",
+    "CK" => "Cook Islands",
+    "CL" => "Chile",
+    "CM" => "Cameroon",
+    "CN" => "China",
+    "CO" => "Colombia",
+    "CR" => "Costa Rica",
+    "CU" => "Cuba",
+    "CV" => "Cape Verde",
+    "CW" => "Curaçao",
+    "CX" => "Christmas Island",
+    "CY" => "Cyprus",
+    "CZ" => "Czechia",
+    "DE" => "Germany",
+    "DJ" => "Djibouti",
+    "DK" => "Denmark",
+    "DM" => "Dominica",
+    "DO" => "Dominican Republic",
+    "DZ" => "Algeria",
+    "EC" => "Ecuador",
+    "EE" => "Estonia",
+    "EG" => "Egypt",
+    "EH" => "Western Sahara",
+    "ER" => "Eritrea",
+    "ES" => "Spain",
+    "ET" => "Ethiopia",
+    "FI" => "Finland",
+    "FJ" => "Fiji",
+    "FK" => "Falkland Islands",
+    "FM" => "Micronesia",
+    "FO" => "Faroe Islands",
+    "FR" => "France",
+    "GA" => "Gabon",
+    "GB" => "United Kingdom",
+    "GD" => "Grenada",
+    "GE" => "Georgia",
+    "GF" => "French Guiana",
+    "GG" => "Guernsey",
+    "GH" => "Ghana",
+    "GI" => "Gibraltar",
+    "GL" => "Greenland",
+    "GM" => "Gambia",
+    "GN" => "Guinea",
+    "GP" => "Guadeloupe",
+    "GQ" => "Equatorial Guinea",
+    "GR" => "Greece",
+    "GS" => "South Georgia and the South Sandwich Islands",
+    "GT" => "Guatemala",
+    "GU" => "Guam",
+    "GW" => "Guinea-Bissau",
+    "GY" => "Guyana",
+    "HK" => "Hong Kong",
+    "HM" => "Heard Island and McDonald Islands",
+    "HN" => "Honduras",
+    "HR" => "Croatia",
+    "HT" => "Haiti",
+    "HU" => "Hungary",
+    "ID" => "Indonesia",
+    "IE" => "Ireland",
+    "IL" => "Israel",
+    "IM" => "Isle of Man",
+    "IN" => "India",
+    "IO" => "British Indian Ocean Territory",
+    "IQ" => "Iraq",
+    "IR" => "Iran",
+    "IS" => "Iceland",
+    "IT" => "Italy",
+    "JE" => "Jersey",
+    "JM" => "Jamaica",
+    "JO" => "Jordan",
+    "JP" => "Japan",
+    "KE" => "Kenya",
+    "KG" => "Kyrgyzstan",
+    "KH" => "Cambodia",
+    "KI" => "Kiribati",
+    "KM" => "Comoros",
+    "KN" => "Saint Kitts and Nevis",
+    "KP" => "North Korea",
+    "KR" => "South Korea",
+    "KW" => "Kuwait",
+    "KY" => "Cayman Islands",
+    "KZ" => "Kazakhstan",
+    "LA" => "Laos",
+    "LB" => "Lebanon",
+    "LC" => "Saint Lucia",
+    "LI" => "Liechtenstein",
+    "LK" => "Sri Lanka",
+    "LR" => "Liberia",
+    "LS" => "Lesotho",
+    "LT" => "Lithuania",
+    "LU" => "Luxembourg",
+    "LV" => "Latvia",
+    "LY" => "Libya",
+    "MA" => "Morocco",
+    "MC" => "Monaco",
+    "MD" => "Moldova",
+    "ME" => "Montenegro",
+    "MF" => "Saint Martin",
+    "MG" => "Madagascar",
+    "MH" => "Marshall Islands",
+    "MK" => "North Macedonia",
+    "ML" => "Mali",
+    "MM" => "Myanmar",
+    "MN" => "Mongolia",
+    "MO" => "Macao",
+    "MP" => "Northern Mariana Islands",
+    "MQ" => "Martinique",
+    "MR" => "Mauritania",
+    "MS" => "Montserrat",
+    "MT" => "Malta",
+    "MU" => "Mauritius",
+    "MV" => "Maldives",
+    "MW" => "Malawi",
+    "MX" => "Mexico",
+    "MY" => "Malaysia",
+    "MZ" => "Mozambique",
+    "NA" => "Namibia",
+    "NC" => "New Caledonia",
+    "NE" => "Niger",
+    "NF" => "Norfolk Island",
+    "NG" => "Nigeria",
+    "NI" => "Nicaragua",
+    "NL" => "Netherlands",
+    "NO" => "Norway",
+    "NP" => "Nepal",
+    "NR" => "Nauru",
+    "NU" => "Niue",
+    "NZ" => "New Zealand",
+    "OM" => "Oman",
+    "PA" => "Panama",
+    "PE" => "Peru",
+    "PF" => "French Polynesia",
+    "PG" => "Papua New Guinea",
+    "PH" => "Philippines",
+    "PK" => "Pakistan",
+    "PL" => "Poland",
+    "PM" => "Saint Pierre and Miquelon",
+    "PN" => "Pitcairn Islands",
+    "PR" => "Puerto Rico",
+    "PS" => "Palestine",
+    "PT" => "Portugal",
+    "PW" => "Palau",
+    "PY" => "Paraguay",
+    "QA" => "Qatar",
+    "RE" => "Réunion",
+    "RO" => "Romania",
+    "RS" => "Serbia",
+    "RU" => "Russia",
+    "RW" => "Rwanda",
+    "SA" => "Saudi Arabia",
+    "SB" => "Solomon Islands",
+    "SC" => "Seychelles",
+    "SD" => "Sudan",
+    "SE" => "Sweden",
+    "SG" => "Singapore",
+    "SH" => "Saint Helena, Ascension and Tristan da Cunha",
+    "SI" => "Slovenia",
+    "SJ" => "Svalbard and Jan Mayen",
+    "SK" => "Slovakia",
+    "SL" => "Sierra Leone",
+    "SM" => "San Marino",
+    "SN" => "Senegal",
+    "SO" => "Somalia",
+    "SR" => "Suriname",
+    "SS" => "South Sudan",
+    "ST" => "São Tomé and Príncipe",
+    "SV" => "El Salvador",
+    "SX" => "Sint Maarten",
+    "SY" => "Syria",
+    "SZ" => "Eswatini",
+    "TC" => "Turks and Caicos Islands",
+    "TD" => "Chad",
+    "TF" => "French Southern Territories",
+    "TG" => "Togo",
+    "TH" => "Thailand",
+    "TJ" => "Tajikistan",
+    "TK" => "Tokelau",
+    "TL" => "Timor-Leste",
+    "TM" => "Turkmenistan",
+    "TN" => "Tunisia",
+    "TO" => "Tonga",
+    "TR" => "Turkey",
+    "TT" => "Trinidad and Tobago",
+    "TV" => "Tuvalu",
+    "TW" => "Taiwan",
+    "TZ" => "Tanzania",
+    "UA" => "Ukraine",
+    "UG" => "Uganda",
+    "UM" => "United States Minor Outlying Islands",
+    "US" => "United States",
+    "UY" => "Uruguay",
+    "UZ" => "Uzbekistan",
+    "VA" => "Vatican City",
+    "VC" => "Saint Vincent and the Grenadines",
+    "VE" => "Venezuela",
+    "VG" => "British Virgin Islands",
+    "VI" => "U.S. Virgin Islands",
+    "VN" => "Vietnam",
+    "VU" => "Vanuatu",
+    "WF" => "Wallis and Futuna",
+    "WS" => "Samoa",
+    "YE" => "Yemen",
+    "YT" => "Mayotte",
+    "ZA" => "South Africa",
+    "ZM" => "Zambia",
+    "ZW" => "Zimbabwe",
+};
+
+/// English names of subdivisions, keyed by their ISO 3166-2 code such as
+/// "US-CA". Only the subdivisions of the United States and Canada are included.
+pub(crate) static REGION_NAMES: phf::Map<&'static str, &'static str> = phf_map! {
+    "US-AL" => "Alabama",
+    "US-AK" => "Alaska",
+    "US-AZ" => "Arizona",
+    "US-AR" => "Arkansas",
+    "US-CA" => "California",
+    "US-CO" => "Colorado",
+    "US-CT" => "Connecticut",
+    "US-DE" => "Delaware",
+    "US-DC" => "District of Columbia",
+    "US-FL" => "Florida",
+    "US-GA" => "Georgia",
+    "US-HI" => "Hawaii",
+    "US-ID" => "Idaho",
+    "US-IL" => "Illinois",
+    "US-IN" => "Indiana",
+    "US-IA" => "Iowa",
+    "US-KS" => "Kansas",
+    "US-KY" => "Kentucky",
+    "US-LA" => "Louisiana",
+    "US-ME" => "Maine",
+    "US-MD" => "Maryland",
+    "US-MA" => "Massachusetts",
+    "US-MI" => "Michigan",
+    "US-MN" => "Minnesota",
+    "US-MS" => "Mississippi",
+    "US-MO" => "Missouri",
+    "US-MT" => "Montana",
+    "US-NE" => "Nebraska",
+    "US-NV" => "Nevada",
+    "US-NH" => "New Hampshire",
+    "US-NJ" => "New Jersey",
+    "US-NM" => "New Mexico",
+    "US-NY" => "New York",
+    "US-NC" => "North Carolina",
+    "US-ND" => "North Dakota",
+    "US-OH" => "Ohio",
+    "US-OK" => "Oklahoma",
+    "US-OR" => "Oregon",
+    "US-PA" => "Pennsylvania",
+    "US-RI" => "Rhode Island",
+    "US-SC" => "South Carolina",
+    "US-SD" => "South Dakota",
+    "US-TN" => "Tennessee",
+    "US-TX" => "Texas",
+    "US-UT" => "Utah",
+    "US-VT" => "Vermont",
+    "US-VA" => "Virginia",
+    "US-WA" => "Washington",
+    "US-WV" => "West Virginia",
+    "US-WI" => "Wisconsin",
+    "US-WY" => "Wyoming",
+    "US-AS" => "American Samoa",
+    "US-GU" => "Guam",
+    "US-MP" => "Northern Mariana Islands",
+    "US-PR" => "Puerto Rico",
+    "US-UM" => "United States Minor Outlying Islands",
+    "US-VI" => "U.S. Virgin Islands",
+    "CA-AB" => "Alberta",
+    "CA-BC" => "British Columbia",
+    "CA-MB" => "Manitoba",
+    "CA-NB" => "New Brunswick",
+    "CA-NL" => "Newfoundland and Labrador",
+    "CA-NS" => "Nova Scotia",
+    "CA-NT" => "Northwest Territories",
+    "CA-NU" => "Nunavut",
+    "CA-ON" => "Ontario",
+    "CA-PE" => "Prince Edward Island",
+    "CA-QC" => "Quebec",
+    "CA-SK" => "Saskatchewan",
+    "CA-YT" => "Yukon",
+};
+
+/// ISO 3166-1 alpha-2 codes of the member states of the European Union.
+pub(crate) static EUROPEAN_UNION: phf::Set<&'static str> = phf_set! {
+    "AT",
+    "BE",
+    "BG",
+    "CY",
+    "CZ",
+    "DE",
+    "DK",
+    "EE",
+    "ES",
+    "FI",
+    "FR",
+    "GR",
+    "HR",
+    "HU",
+    "IE",
+    "IT",
+    "LT",
+    "LU",
+    "LV",
+    "MT",
+    "NL",
+    "PL",
+    "PT",
+    "RO",
+    "SE",
+    "SI",
+    "SK",
+};
+
+/// ISO 3166-1 alpha-2 codes of the countries in the European Economic Area
+/// that aren't members of the European Union.
+pub(crate) static EEA_NON_EU: phf::Set<&'static str> = phf_set! {
+    "IS",
+    "LI",
+    "NO",
+};
+
+/// Likely `Accept-Language` values for countries, keyed by their ISO 3166-1
+/// alpha-2 code. Countries with several widely used languages list them in
+/// order of preference.
+pub(crate) static LANGUAGE_HINTS: phf::Map<&'static str, &'static str> = phf_map! {
+    "AE" => "ar,en;q=0.9",
+    "AR" => "es",
+    "AT" => "de",
+    "AU" => "en",
+    "BE" => "nl,fr;q=0.9,de;q=0.8",
+    "BG" => "bg",
+    "BO" => "es",
+    "BR" => "pt",
+    "BY" => "be,ru;q=0.9",
+    "CA" => "en,fr;q=0.9",
+    "CH" => "de,fr;q=0.9,it;q=0.8",
+    "CL" => "es",
+    "CN" => "zh",
+    "CO" => "es",
+    "CR" => "es",
+    "CU" => "es",
+    "CY" => "el,tr;q=0.9",
+    "CZ" => "cs",
+    "DE" => "de",
+    "DK" => "da",
+    "DO" => "es",
+    "DZ" => "ar,fr;q=0.9",
+    "EC" => "es",
+    "EE" => "et",
+    "EG" => "ar",
+    "ES" => "es",
+    "FI" => "fi,sv;q=0.9",
+    "FR" => "fr",
+    "GB" => "en",
+    "GR" => "el",
+    "GT" => "es",
+    "HK" => "zh,en;q=0.9",
+    "HN" => "es",
+    "HR" => "hr",
+    "HU" => "hu",
+    "ID" => "id",
+    "IE" => "en,ga;q=0.9",
+    "IL" => "he",
+    "IN" => "hi,en;q=0.9",
+    "IQ" => "ar",
+    "IR" => "fa",
+    "IS" => "is",
+    "IT" => "it",
+    "JP" => "ja",
+    "KE" => "sw,en;q=0.9",
+    "KR" => "ko",
+    "KZ" => "kk,ru;q=0.9",
+    "LT" => "lt",
+    "LU" => "lb,fr;q=0.9,de;q=0.8",
+    "LV" => "lv",
+    "MA" => "ar,fr;q=0.9",
+    "MX" => "es",
+    "MY" => "ms",
+    "NG" => "en",
+    "NI" => "es",
+    "NL" => "nl",
+    "NO" => "nb",
+    "NZ" => "en",
+    "PA" => "es",
+    "PE" => "es",
+    "PH" => "fil,en;q=0.9",
+    "PK" => "ur,en;q=0.9",
+    "PL" => "pl",
+    "PR" => "es,en;q=0.9",
+    "PT" => "pt",
+    "PY" => "es",
+    "RO" => "ro",
+    "RS" => "sr",
+    "RU" => "ru",
+    "SA" => "ar",
+    "SE" => "sv",
+    "SG" => "en,zh;q=0.9",
+    "SI" => "sl",
+    "SK" => "sk",
+    "SV" => "es",
+    "TH" => "th",
+    "TN" => "ar,fr;q=0.9",
+    "TR" => "tr",
+    "TW" => "zh",
+    "UA" => "uk",
+    "US" => "en",
+    "UY" => "es",
+    "VE" => "es",
+    "VN" => "vi",
+    "ZA" => "en,af;q=0.9",
+};
+
+/// Likely `Accept-Language` values for regions whose languages differ from
+/// the rest of their country, keyed by ISO 3166-2 code.
+pub(crate) static REGION_LANGUAGE_HINTS: phf::Map<&'static str, &'static str> = phf_map! {
+    "BE-BRU" => "fr,nl;q=0.9",
+    "BE-VLG" => "nl",
+    "BE-WAL" => "fr",
+    "CA-QC" => "fr,en;q=0.9",
+    "ES-CT" => "ca,es;q=0.9",
+};
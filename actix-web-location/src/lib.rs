@@ -7,6 +7,14 @@
 /* This is a mess, but it makes the rest of the crate tidier. Only include this
  * modules and uses if exactly one of v3 or v4 is specified. */
 
+#[cfg(all(
+    feature = "config-file",
+    any(
+        all(feature = "actix-web-v3", not(feature = "actix-web-v4")),
+        all(not(feature = "actix-web-v3"), feature = "actix-web-v4")
+    )
+))]
+mod config_file;
 #[cfg(any(
     all(feature = "actix-web-v3", not(feature = "actix-web-v4")),
     all(not(feature = "actix-web-v3"), feature = "actix-web-v4")
@@ -26,13 +34,28 @@ mod extractors;
     all(feature = "actix-web-v3", not(feature = "actix-web-v4")),
     all(not(feature = "actix-web-v3"), feature = "actix-web-v4")
 ))]
+mod iso3166;
+#[cfg(any(
+    all(feature = "actix-web-v3", not(feature = "actix-web-v4")),
+    all(not(feature = "actix-web-v3"), feature = "actix-web-v4")
+))]
 pub mod providers;
 
 #[cfg(any(
     all(feature = "actix-web-v3", not(feature = "actix-web-v4")),
     all(not(feature = "actix-web-v3"), feature = "actix-web-v4")
 ))]
-pub use crate::{domain::Location, error::Error, extractors::LocationConfig, providers::Provider};
+pub use crate::{
+    domain::Location,
+    error::Error,
+    extractors::{LocationConfig, LocationConfigSnapshot, ProviderErrorPolicy},
+    providers::Provider,
+};
+#[cfg(any(
+    all(feature = "actix-web-v3", not(feature = "actix-web-v4")),
+    all(not(feature = "actix-web-v3"), feature = "actix-web-v4")
+))]
+pub use ipnetwork::IpNetwork;
 
 /* The two stanzas below provide nicer error messages if not exactly one of v3
  * and v4 are enabled. They aren't hard errors so that this crate's CI still
@@ -56,6 +79,18 @@ mod warning {
     }
 }
 
+/* The MaxMind database updater downloads with reqwest, which needs a Tokio 1
+ * runtime. Actix Web 3 runs on Tokio 0.2, so the updater would panic at run
+ * time instead of failing here. */
+#[cfg(all(
+    feature = "maxmind-update",
+    feature = "actix-web-v3",
+    not(feature = "actix-web-v4")
+))]
+compile_error!(
+    "The maxmind-update feature requires actix-web-v4. It uses reqwest, which needs a Tokio 1 runtime, but actix-web 3 runs on Tokio 0.2."
+);
+
 /* If neither v3 or v4 are enabled at the same time, generate a compiler warning. */
 #[cfg(not(any(feature = "actix-web-v3", feature = "actix-web-v4")))]
 mod warning {
@@ -1,10 +1,34 @@
 //! Providers are `actix-web-location`'s abstraction to allow multiple ways of determining location.
 
+use std::{collections::HashMap, net::IpAddr};
+
 use crate::{domain::LocationBuilder, Error, Location};
 use async_trait::async_trait;
 
+#[cfg(feature = "csv")]
+mod cache_warmer;
+mod caching;
+mod circuit_breaker;
+#[cfg(feature = "maxmind-update")]
+mod maxmind_updater;
+#[cfg(test)]
+pub(crate) mod testing;
+#[cfg(feature = "tracing")]
+mod tracing;
+
+#[cfg(feature = "tracing")]
+pub use self::tracing::TracingProvider;
+#[cfg(feature = "csv")]
+pub use cache_warmer::GeoipCacheWarmer;
+pub use caching::CachingProvider;
+pub use circuit_breaker::CircuitBreakerProvider;
 #[cfg(feature = "maxmind")]
-pub use maxmind::MaxMindProvider;
+pub use maxmind::{
+    MaxMindCombinedProvider, MaxMindMetadata, MaxMindProvider, MaxMindProviderBuilder,
+    MaxMindProviderFactory, Schema,
+};
+#[cfg(feature = "maxmind-update")]
+pub use maxmind_updater::MaxMindUpdater;
 
 #[cfg(feature = "actix-web-v3")]
 use actix_web_3::HttpRequest;
@@ -20,9 +44,73 @@ pub trait Provider: Send + Sync {
     /// Provide a name of the provider for use in diagnostics.
     fn name(&self) -> &str;
 
+    /// The name of the provider for use as a StatsD tag value.
+    ///
+    /// The default implementation is the [name](Provider::name) in lowercase,
+    /// with any characters other than ASCII letters and digits replaced by
+    /// underscores.
+    fn metrics_name(&self) -> String {
+        self.name()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
     /// Derive a location from a request's metadata.
     async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error>;
 
+    /// Derive a location from only the client's address, for code outside of
+    /// request handlers such as [`Location::from_ip`].
+    ///
+    /// The default implementation returns `None`, for providers that need
+    /// more of the request than its address.
+    async fn get_location_for_ip(&self, _addr: IpAddr) -> Result<Option<Location>, Error> {
+        Ok(None)
+    }
+
+    /// The client address that this provider looks up for `request`, so that
+    /// wrappers such as [`CachingProvider`] key their entries by the same
+    /// address.
+    ///
+    /// The default implementation is the first entry of the `X-Forwarded-For`
+    /// header, or the peer address if that is missing.
+    fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+        crate::extractors::client_ip(request)
+    }
+
+    /// Prepare the provider to serve requests, such as by pre-loading caches.
+    /// This is intended to be called at server startup, usually via
+    /// [`LocationConfig::warm_up_all`](crate::LocationConfig::warm_up_all).
+    ///
+    /// The default implementation does nothing.
+    async fn warm_up(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Describe this provider for diagnostics, such as a health check endpoint.
+    ///
+    /// The default implementation only includes the provider's name.
+    fn describe(&self) -> ProviderDescription {
+        ProviderDescription {
+            name: self.name().to_string(),
+            kind: "custom",
+            details: HashMap::new(),
+        }
+    }
+
+    /// Statistics about this provider's cache, if it has one.
+    ///
+    /// The default implementation returns `None`.
+    fn cache_stats(&self) -> Option<CacheStats> {
+        None
+    }
+
     /// Can this provider produce locations with country information?
     fn expect_country(&self) -> bool {
         true
@@ -39,6 +127,149 @@ pub trait Provider: Send + Sync {
     }
 }
 
+/// Implement [`Provider`] for a pointer type by delegating to the provider it
+/// points to.
+macro_rules! delegate_provider {
+    ($($impl_generics:lifetime)? ; $ty:ty) => {
+        #[async_trait(?Send)]
+        impl<$($impl_generics)?> Provider for $ty {
+            fn name(&self) -> &str {
+                (**self).name()
+            }
+
+            fn metrics_name(&self) -> String {
+                (**self).metrics_name()
+            }
+
+            async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
+                (**self).get_location(request).await
+            }
+
+            async fn get_location_for_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+                (**self).get_location_for_ip(addr).await
+            }
+
+            fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+                (**self).client_addr(request)
+            }
+
+            async fn warm_up(&self) -> Result<(), Error> {
+                (**self).warm_up().await
+            }
+
+            fn describe(&self) -> ProviderDescription {
+                (**self).describe()
+            }
+
+            fn cache_stats(&self) -> Option<CacheStats> {
+                (**self).cache_stats()
+            }
+
+            fn expect_country(&self) -> bool {
+                (**self).expect_country()
+            }
+
+            fn expect_region(&self) -> bool {
+                (**self).expect_region()
+            }
+
+            fn expect_city(&self) -> bool {
+                (**self).expect_city()
+            }
+        }
+    };
+}
+
+delegate_provider!(; Box<dyn Provider>);
+delegate_provider!('a; &'a dyn Provider);
+
+/// Structured information about a [`Provider`], from [`Provider::describe`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ProviderDescription {
+    /// The name of the provider, as from [`Provider::name`].
+    pub name: String,
+
+    /// The kind of provider, such as "maxmind" or "fallback".
+    pub kind: &'static str,
+
+    /// Provider specific information, such as which database is in use.
+    pub details: HashMap<String, String>,
+}
+
+/// Counters for a provider that caches locations, from [`Provider::cache_stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CacheStats {
+    /// The number of lookups answered from the cache.
+    pub hits: u64,
+
+    /// The number of lookups that weren't in the cache.
+    pub misses: u64,
+
+    /// The number of entries removed from the cache to make room for others.
+    pub evictions: u64,
+}
+
+/// An object that can asynchronously construct a [`Provider`], for providers
+/// that need to do I/O during setup.
+///
+/// Use [`macro@async_trait`] when implementing.
+#[async_trait(?Send)]
+pub trait AsyncProviderFactory: Send + Sync {
+    /// Construct the provider.
+    async fn build(&self) -> Result<Box<dyn Provider>, Error>;
+}
+
+/// The candidates for [`auto_detect_provider`], in order of preference.
+#[derive(Default)]
+pub struct AutoDetectConfig {
+    candidates: Vec<Box<dyn AsyncProviderFactory>>,
+}
+
+impl AutoDetectConfig {
+    /// Create a configuration with no candidates.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a candidate that is tried after all previously added candidates.
+    pub fn with_candidate<F: AsyncProviderFactory + 'static>(mut self, factory: F) -> Self {
+        self.candidates.push(Box::new(factory));
+        self
+    }
+}
+
+/// Construct the first provider in `config` that is available, for
+/// deployments where it isn't known ahead of time which providers can be used,
+/// such as whether a MaxMind database is present.
+///
+/// Each candidate is built and then [warmed up](Provider::warm_up), in order,
+/// and the first one for which both succeed is returned. Later candidates are
+/// not built. If no candidate succeeds, the errors of all of them are returned
+/// as an [`Error::Multiple`].
+pub async fn auto_detect_provider(config: AutoDetectConfig) -> Result<Box<dyn Provider>, Error> {
+    let mut errors = Vec::new();
+    for candidate in &config.candidates {
+        let result = match candidate.build().await {
+            Ok(provider) => provider.warm_up().await.map(|()| provider),
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(provider) => return Ok(provider),
+            Err(error) => errors.push(error),
+        }
+    }
+
+    if errors.is_empty() {
+        Err(Error::Setup(anyhow::anyhow!(
+            "there are no candidate providers to detect"
+        )))
+    } else {
+        Err(Error::Multiple(errors))
+    }
+}
+
 /// A "dummy" provider that returns None for all fields.
 pub struct FallbackProvider {
     fallback: Location,
@@ -56,62 +287,486 @@ impl FallbackProvider {
                 .expect("Location construction bug"),
         }
     }
+
+    /// Change the name of this provider, which is also used as the provider
+    /// of the returned locations. This distinguishes multiple fallback
+    /// providers, such as `"datacenter-default"`. Defaults to `"fallback"`.
+    #[allow(deprecated)]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.fallback.provider = name.into();
+        self
+    }
 }
 
 #[async_trait(?Send)]
 impl Provider for FallbackProvider {
     fn name(&self) -> &str {
-        "fallback"
+        self.fallback.provider()
     }
 
     async fn get_location(&self, _request: &HttpRequest) -> Result<Option<Location>, Error> {
         Ok(Some(self.fallback.clone()))
     }
+
+    async fn get_location_for_ip(&self, _addr: IpAddr) -> Result<Option<Location>, Error> {
+        Ok(Some(self.fallback.clone()))
+    }
+
+    fn describe(&self) -> ProviderDescription {
+        ProviderDescription {
+            name: self.name().to_string(),
+            kind: "fallback",
+            details: HashMap::new(),
+        }
+    }
 }
 
 #[cfg(feature = "maxmind")]
 mod maxmind {
     use std::{
-        net::{IpAddr, SocketAddr},
-        path::Path,
-        sync::Arc,
+        collections::HashMap,
+        net::IpAddr,
+        path::{Path, PathBuf},
+        sync::{Arc, RwLock},
+        time::{Duration, SystemTime},
     };
 
     use crate::domain::LocationBuilder;
 
-    use super::{Error, Location, Provider};
+    use super::{AsyncProviderFactory, Error, Location, Provider, ProviderDescription};
     use anyhow::anyhow;
     use async_trait::async_trait;
-    use lazy_static::lazy_static;
-    use maxminddb::geoip2::City;
+    use maxminddb::geoip2::{Asn, City, Enterprise};
 
     #[cfg(feature = "actix-web-v3")]
-    use actix_web_3::{http::HeaderName, HttpRequest};
+    use actix_web_3::{error::BlockingError, http::HeaderName, web, HttpRequest};
 
     #[cfg(feature = "actix-web-v4")]
-    use actix_web_4::{http::header::HeaderName, HttpRequest};
+    use actix_web_4::{http::header::HeaderName, web, HttpRequest};
+
+    /// The kind of MaxMind database a [`MaxMindProvider`] reads.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    #[cfg_attr(
+        feature = "serde",
+        derive(serde::Deserialize),
+        serde(rename_all = "lowercase")
+    )]
+    pub enum Schema {
+        /// GeoLite2-City or GeoIP2-City databases.
+        #[default]
+        City,
 
-    lazy_static! {
-        static ref X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+        /// GeoIP2-Enterprise databases, which additionally provide the ISP
+        /// and connection type of an address.
+        Enterprise,
+    }
+
+    /// Information about a MaxMind database, from
+    /// [`MaxMindProvider::database_metadata`].
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    pub struct MaxMindMetadata {
+        /// The kind of database, such as "GeoLite2-City".
+        pub database_type: String,
+
+        /// Descriptions of the database, keyed by language code.
+        pub description: HashMap<String, String>,
+
+        /// The IP version of the addresses in the database, 4 or 6.
+        pub ip_version: u16,
+
+        /// The time the database was built, in seconds since the UNIX epoch.
+        pub build_epoch: u64,
+
+        /// The languages that names in the database are available in.
+        pub languages: Vec<String>,
+
+        /// The major and minor version of the database file format.
+        pub binary_format_version: (u16, u16),
     }
 
     /// A provider that uses a MaxMind GeoIP database to derive location from a the IP a request was sent from.
     #[derive(Clone)]
     pub struct MaxMindProvider {
-        mmdb: Arc<maxminddb::Reader<Vec<u8>>>,
+        mmdb: Arc<RwLock<Arc<maxminddb::Reader<Vec<u8>>>>>,
+        schema: Schema,
+        path: PathBuf,
+        none_for_private_ips: bool,
+        additional_ip_headers: Arc<Vec<HeaderName>>,
+        peer_address_fallback: bool,
     }
 
     impl MaxMindProvider {
         /// Read a file from the given path into memory, and use it to construct a location provider.
         pub fn from_path(path: &Path) -> Result<Self, Error> {
-            Ok(Self {
-                mmdb: maxminddb::Reader::open_readfile(path)
-                    .map_err(|e| Error::Setup(anyhow!("{}", e)))
-                    .map(Arc::new)?,
+            MaxMindProviderBuilder::new(path).finish()
+        }
+
+        /// Create a builder for a provider that reads the database at `path`.
+        pub fn build<P: Into<PathBuf>>(path: P) -> MaxMindProviderBuilder {
+            MaxMindProviderBuilder::new(path)
+        }
+
+        /// The time the loaded database was built, according to its metadata.
+        pub fn last_database_update(&self) -> Option<SystemTime> {
+            SystemTime::UNIX_EPOCH
+                .checked_add(Duration::from_secs(self.reader().metadata.build_epoch))
+        }
+
+        /// Information about the loaded database, from its metadata.
+        pub fn database_metadata(&self) -> MaxMindMetadata {
+            let reader = self.reader();
+            let metadata = &reader.metadata;
+            MaxMindMetadata {
+                database_type: metadata.database_type.clone(),
+                description: metadata
+                    .description
+                    .iter()
+                    .map(|(language, description)| (language.clone(), description.clone()))
+                    .collect(),
+                ip_version: metadata.ip_version,
+                build_epoch: metadata.build_epoch,
+                languages: metadata.languages.clone(),
+                binary_format_version: (
+                    metadata.binary_format_major_version,
+                    metadata.binary_format_minor_version,
+                ),
+            }
+        }
+
+        /// How long ago the loaded database was built. This is zero if the
+        /// build time is unknown or in the future.
+        pub fn database_age(&self) -> Duration {
+            self.last_database_update()
+                .and_then(|updated| SystemTime::now().duration_since(updated).ok())
+                .unwrap_or_default()
+        }
+
+        /// Look up the location of `addr` directly, without an [`HttpRequest`].
+        ///
+        /// This is useful for batch jobs and other processing done outside of
+        /// a request handler.
+        pub async fn lookup_by_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+            self._lookup_ip(addr)
+        }
+
+        /// Read the database file again, replacing the loaded database, such
+        /// as after `MaxMindUpdater` downloads a new version. Clones of this
+        /// provider share the reloaded database.
+        ///
+        /// If the file can't be read, the previous database is kept. This
+        /// reads the whole file, so in async code it should be run with
+        /// `web::block` or similar.
+        pub fn reload_file(&self) -> Result<(), Error> {
+            let reader = open_reader(&self.path)?;
+            *self.mmdb.write().expect("lock was poisoned") = reader;
+            Ok(())
+        }
+
+        /// The path of the database file this provider reads.
+        pub fn database_path(&self) -> &Path {
+            &self.path
+        }
+
+        /// The address to look up for `request`, from the client address
+        /// headers, or from the peer address if that fallback is enabled.
+        fn request_addr(&self, request: &HttpRequest) -> Result<Option<IpAddr>, Error> {
+            let addr = crate::extractors::forwarded_addr(request, &self.additional_ip_headers)?;
+            if addr.is_none() && self.peer_address_fallback {
+                return Ok(request.peer_addr().map(|peer| peer.ip()));
+            }
+            Ok(addr)
+        }
+
+        /// The currently loaded database.
+        fn reader(&self) -> Arc<maxminddb::Reader<Vec<u8>>> {
+            self.mmdb.read().expect("lock was poisoned").clone()
+        }
+
+        fn _lookup_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+            let reader = self.reader();
+            let result = match self.schema {
+                Schema::City => reader.lookup::<City>(addr).map(|city| (city, "en").into()),
+                Schema::Enterprise => reader
+                    .lookup::<Enterprise>(addr)
+                    .map(|enterprise| (enterprise, "en").into()),
+            };
+            let builder: LocationBuilder = match result {
+                Ok(builder) => builder,
+                Err(_) if self.none_for_private_ips && is_private(addr) => return Ok(None),
+                Err(err) => return Err(Error::Provider(err.into())),
+            };
+            builder
+                .provider("maxmind".to_string())
+                .finish()
+                .map(Some)
+                .map_err(|_| Error::Provider(anyhow::anyhow!("Bug while building location")))
+        }
+    }
+
+    /// A provider that looks up addresses in a MaxMind City database, and
+    /// optionally a GeoLite2-ASN or GeoIP2-ASN database, merging the results
+    /// into a single location. The ASN database provides the
+    /// [`isp`](Location::isp) of the location.
+    ///
+    /// If an address is only in one of the databases, the location has just
+    /// the information from that database.
+    #[derive(Clone)]
+    pub struct MaxMindCombinedProvider {
+        city: MaxMindProvider,
+        asn: Option<(Arc<maxminddb::Reader<Vec<u8>>>, PathBuf)>,
+    }
+
+    impl MaxMindCombinedProvider {
+        /// Read the City database at `city_path`, and the ASN database at
+        /// `asn_path` if there is one, into memory.
+        pub fn from_paths(city_path: &Path, asn_path: Option<&Path>) -> Result<Self, Error> {
+            let city = MaxMindProvider::from_path(city_path)?;
+            let asn = asn_path
+                .map(|path| open_reader(path).map(|reader| (reader, path.to_path_buf())))
+                .transpose()?;
+            Ok(Self { city, asn })
+        }
+
+        /// Look up the location of `addr` directly, without an [`HttpRequest`].
+        pub async fn lookup_by_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+            self._lookup_ip(addr)
+        }
+
+        fn _lookup_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+            let city = self.city._lookup_ip(addr);
+            match &self.asn {
+                Some((reader, _)) => self.merge(city, reader.lookup::<Asn>(addr).ok()),
+                None => city,
+            }
+        }
+
+        /// Combine the result of the City lookup with the ASN record for the
+        /// same address, if there is one.
+        pub(super) fn merge(
+            &self,
+            city: Result<Option<Location>, Error>,
+            asn: Option<Asn>,
+        ) -> Result<Option<Location>, Error> {
+            let asn = asn.map(|asn| {
+                Location::build()
+                    .isp(asn.autonomous_system_organization.map(String::from))
+                    .provider(self.name().to_string())
+                    .finish()
+                    .expect("bug when creating location")
+            });
+
+            match (city, asn) {
+                (Ok(Some(city)), Some(asn)) => Ok(Some(city.merge(asn))),
+                (Ok(None), Some(asn)) => Ok(Some(asn)),
+                (Err(error), Some(asn)) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(
+                        %error,
+                        "MaxMind City lookup failed, using only the ASN database"
+                    );
+                    #[cfg(not(feature = "tracing"))]
+                    drop(error);
+                    Ok(Some(asn))
+                }
+                (city, None) => city,
+            }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl Provider for MaxMindCombinedProvider {
+        fn name(&self) -> &str {
+            "maxmind-combined"
+        }
+
+        async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
+            match self.city.request_addr(request)? {
+                Some(addr) => self._lookup_ip(addr),
+                None => Ok(None),
+            }
+        }
+
+        async fn get_location_for_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+            self._lookup_ip(addr)
+        }
+
+        fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+            self.city.client_addr(request)
+        }
+
+        fn describe(&self) -> ProviderDescription {
+            let mut description = self.city.describe();
+            description.name = self.name().to_string();
+            if let Some((_, path)) = &self.asn {
+                description
+                    .details
+                    .insert("asn_database_path".to_string(), path.display().to_string());
+            }
+            description
+        }
+    }
+
+    /// Read the MaxMind database at `path` into memory.
+    fn open_reader(path: &Path) -> Result<Arc<maxminddb::Reader<Vec<u8>>>, Error> {
+        maxminddb::Reader::open_readfile(path)
+            .map(Arc::new)
+            .map_err(|e| Error::Setup(anyhow!("{}", e)))
+    }
+
+    /// Is `addr` in a loopback, private, or link-local range, which won't be in
+    /// a GeoIP database?
+    pub(super) fn is_private(addr: IpAddr) -> bool {
+        match addr {
+            IpAddr::V4(addr) => addr.is_loopback() || addr.is_private() || addr.is_link_local(),
+            IpAddr::V6(addr) => {
+                let first_segment = addr.segments()[0];
+                addr.is_loopback()
+                    // Unique local addresses, fc00::/7
+                    || (first_segment & 0xfe00) == 0xfc00
+                    // Link-local addresses, fe80::/10
+                    || (first_segment & 0xffc0) == 0xfe80
+                    || addr
+                        .to_ipv4_mapped()
+                        .is_some_and(|addr| is_private(IpAddr::V4(addr)))
+            }
+        }
+    }
+
+    /// Format a number of seconds since the UNIX epoch as a `YYYY-MM-DD` date.
+    pub(super) fn format_date(epoch_seconds: u64) -> String {
+        // Convert days since the epoch to a civil date, following
+        // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+        let days = (epoch_seconds / 86_400) as i64 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    /// Configures a [`MaxMindProvider`] before reading its database.
+    pub struct MaxMindProviderBuilder {
+        path: PathBuf,
+        schema: Schema,
+        none_for_private_ips: bool,
+        additional_ip_headers: Vec<String>,
+        peer_address_fallback: bool,
+    }
+
+    impl MaxMindProviderBuilder {
+        /// Create a builder for a provider that reads the database at `path`.
+        pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+            Self {
+                path: path.into(),
+                schema: Schema::default(),
+                none_for_private_ips: false,
+                additional_ip_headers: Vec::new(),
+                peer_address_fallback: false,
+            }
+        }
+
+        /// Set the kind of database being read. Defaults to [`Schema::City`].
+        pub fn with_schema(mut self, schema: Schema) -> Self {
+            self.schema = schema;
+            self
+        }
+
+        /// If enabled, lookups of loopback, private (such as RFC 1918), and
+        /// link-local addresses that aren't in the database produce `Ok(None)`
+        /// instead of [`Error::Provider`]. Defaults to `false`.
+        pub fn return_none_for_private_ips(mut self, enabled: bool) -> Self {
+            self.none_for_private_ips = enabled;
+            self
+        }
+
+        /// Also look for the client address in the request header `name`,
+        /// such as `X-Client-IP`, if there is no `X-Forwarded-For` header.
+        /// Additional headers are tried in the order they are added.
+        ///
+        /// Clients can send any headers they like, so only use headers that
+        /// your own load balancers or proxies set, replacing any values sent by
+        /// the client. Otherwise clients can choose the location they appear
+        /// to be at.
+        pub fn with_additional_ip_header(mut self, name: impl Into<String>) -> Self {
+            self.additional_ip_headers.push(name.into());
+            self
+        }
+
+        /// If enabled, look up the address of the connection's peer when the
+        /// request has none of the client address headers, for servers that
+        /// clients connect to directly. Defaults to `false`.
+        ///
+        /// Behind a load balancer or proxy, the peer is the proxy, so requests
+        /// that are missing the headers would get the proxy's location
+        /// instead of no location. Only enable this for servers that aren't
+        /// behind a proxy.
+        pub fn with_peer_address_fallback(mut self, enabled: bool) -> Self {
+            self.peer_address_fallback = enabled;
+            self
+        }
+
+        /// Read the database into memory, and use it to construct a location provider.
+        pub fn finish(self) -> Result<MaxMindProvider, Error> {
+            let additional_ip_headers = self
+                .additional_ip_headers
+                .iter()
+                .map(|name| {
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|e| Error::Setup(anyhow!("invalid header name {:?}: {}", name, e)))
+                })
+                .collect::<Result<_, _>>()?;
+            Ok(MaxMindProvider {
+                mmdb: Arc::new(RwLock::new(open_reader(&self.path)?)),
+                schema: self.schema,
+                path: self.path,
+                none_for_private_ips: self.none_for_private_ips,
+                additional_ip_headers: Arc::new(additional_ip_headers),
+                peer_address_fallback: self.peer_address_fallback,
             })
         }
     }
 
+    /// Constructs a [`MaxMindProvider`] without blocking the async runtime while
+    /// the database is read from disk.
+    pub struct MaxMindProviderFactory {
+        path: PathBuf,
+    }
+
+    impl MaxMindProviderFactory {
+        /// Create a factory that will read the database at `path`.
+        pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+            Self { path: path.into() }
+        }
+    }
+
+    #[async_trait(?Send)]
+    impl AsyncProviderFactory for MaxMindProviderFactory {
+        async fn build(&self) -> Result<Box<dyn Provider>, Error> {
+            let path = self.path.clone();
+            let result = web::block(move || MaxMindProvider::from_path(&path)).await;
+
+            #[cfg(feature = "actix-web-v3")]
+            let provider = result.map_err(|e| match e {
+                BlockingError::Error(e) => e,
+                BlockingError::Canceled => Error::Setup(anyhow!("provider setup was canceled")),
+            })?;
+            #[cfg(feature = "actix-web-v4")]
+            let provider = result.map_err(|e| Error::Setup(anyhow!("{}", e)))??;
+
+            Ok(Box::new(provider))
+        }
+    }
+
     #[async_trait(?Send)]
     impl Provider for MaxMindProvider {
         fn name(&self) -> &str {
@@ -119,41 +774,37 @@ mod maxmind {
         }
 
         async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
-            let header = request.headers().get(&*X_FORWARDED_FOR);
-
-            let addr = if let Some(header) = header {
-                // Expect a typical X-Forwarded-For where the first address is
-                // the client's, the front ends should ensure this
-                let value = header
-                    .to_str()
-                    .map_err(|e| Error::Http(e.into()))?
-                    .split(',')
-                    .next()
-                    .unwrap_or_default()
-                    .trim();
-                let parsed = value
-                    .parse::<IpAddr>()
-                    // Fallback to parsing as SocketAddr for when a port
-                    // number's included
-                    .or_else(|_| value.parse::<SocketAddr>().map(|socket| socket.ip()))
-                    .map_err(|e| Error::Http(e.into()))?;
-                Some(parsed)
-            } else {
-                None
-            };
+            match self.request_addr(request)? {
+                Some(addr) => self._lookup_ip(addr),
+                None => Ok(None),
+            }
+        }
 
-            addr.map(|addr| {
-                let city = self
-                    .mmdb
-                    .lookup::<City>(addr)
-                    .map_err(|err| Error::Provider(err.into()))?;
-                let builder: LocationBuilder = (city, "en").into();
-                builder
-                    .provider("maxmind".to_string())
-                    .finish()
-                    .map_err(|_| Error::Provider(anyhow::anyhow!("Bug while building location")))
-            })
-            .transpose()
+        async fn get_location_for_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+            self._lookup_ip(addr)
+        }
+
+        fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+            self.request_addr(request).ok().flatten()
+        }
+
+        async fn warm_up(&self) -> Result<(), Error> {
+            // The database is fully read into memory when the provider is created.
+            Ok(())
+        }
+
+        fn describe(&self) -> ProviderDescription {
+            let mut details = HashMap::new();
+            details.insert("database_path".to_string(), self.path.display().to_string());
+            details.insert(
+                "build_date".to_string(),
+                format_date(self.reader().metadata.build_epoch),
+            );
+            ProviderDescription {
+                name: self.name().to_string(),
+                kind: "maxmind",
+                details,
+            }
         }
     }
 }
@@ -165,10 +816,16 @@ pub(crate) mod tests {
     #[cfg(feature = "actix-web-v4")]
     use actix_web_4::test::TestRequest;
 
-    use super::FallbackProvider;
-    use crate::{Location, Provider};
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use super::{auto_detect_provider, AsyncProviderFactory, AutoDetectConfig, FallbackProvider};
+    use crate::{Error, Location, Provider};
 
     #[actix_rt::test]
+    #[allow(deprecated)]
     async fn fallback_works_empty() {
         let provider = FallbackProvider::new(Location::build());
         let request = TestRequest::default().to_http_request();
@@ -182,14 +839,60 @@ pub(crate) mod tests {
             Location {
                 country: None,
                 region: None,
+                region_name: None,
                 city: None,
                 dma: None,
+                market_area_code: None,
+                latitude: None,
+                longitude: None,
+                accuracy_radius: None,
+                timezone: None,
+                isp: None,
+                connection_type: None,
                 provider: "fallback".to_string()
             }
         )
     }
 
     #[actix_rt::test]
+    async fn boxed_and_borrowed_providers() {
+        let boxed: Box<dyn Provider> = Box::new(FallbackProvider::new(
+            Location::build().country("CA".to_string()),
+        ));
+        let borrowed: &dyn Provider = &*boxed;
+        let request = TestRequest::default().to_http_request();
+
+        for provider in [&boxed as &dyn Provider, &borrowed as &dyn Provider] {
+            assert_eq!(provider.name(), "fallback");
+            assert_eq!(provider.describe().kind, "fallback");
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("Could not get location")
+                .expect("Location was none");
+            assert_eq!(location.country(), "CA");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn fallback_with_name() {
+        let provider = FallbackProvider::new(Location::build().country("US".to_string()))
+            .with_name("datacenter-default");
+        assert_eq!(provider.name(), "datacenter-default");
+        assert_eq!(provider.describe().kind, "fallback");
+
+        let request = TestRequest::default().to_http_request();
+        let location = provider
+            .get_location(&request)
+            .await
+            .expect("Could not get location")
+            .expect("Location was none");
+        assert_eq!(location.provider(), "datacenter-default");
+        assert_eq!(location.country(), "US");
+    }
+
+    #[actix_rt::test]
+    #[allow(deprecated)]
     async fn fallback_works_full() {
         let provider = FallbackProvider::new(
             Location::build()
@@ -208,18 +911,171 @@ pub(crate) mod tests {
             Location {
                 country: Some("CA".to_string()),
                 region: Some("BC".to_string()),
+                region_name: None,
                 city: Some("Burnaby".to_string()),
                 dma: None,
+                market_area_code: None,
+                latitude: None,
+                longitude: None,
+                accuracy_radius: None,
+                timezone: None,
+                isp: None,
+                connection_type: None,
                 provider: "fallback".to_string()
             }
         )
     }
 
+    /// A factory that fails to build, builds a provider that fails to warm
+    /// up, or builds a working fallback provider, counting its builds.
+    #[derive(Clone)]
+    struct TestFactory {
+        name: &'static str,
+        fail_build: bool,
+        fail_warm_up: bool,
+        builds: Arc<AtomicU32>,
+    }
+
+    impl TestFactory {
+        fn new(name: &'static str, fail_build: bool, fail_warm_up: bool) -> Self {
+            Self {
+                name,
+                fail_build,
+                fail_warm_up,
+                builds: Arc::default(),
+            }
+        }
+    }
+
+    struct TestProvider {
+        fallback: FallbackProvider,
+        fail_warm_up: bool,
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl Provider for TestProvider {
+        fn name(&self) -> &str {
+            self.fallback.name()
+        }
+
+        async fn get_location(
+            &self,
+            request: &super::HttpRequest,
+        ) -> Result<Option<Location>, Error> {
+            self.fallback.get_location(request).await
+        }
+
+        async fn warm_up(&self) -> Result<(), Error> {
+            if self.fail_warm_up {
+                Err(Error::Setup(anyhow::anyhow!(
+                    "{} is not ready",
+                    self.name()
+                )))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[async_trait::async_trait(?Send)]
+    impl AsyncProviderFactory for TestFactory {
+        async fn build(&self) -> Result<Box<dyn Provider>, Error> {
+            self.builds.fetch_add(1, Ordering::SeqCst);
+            if self.fail_build {
+                return Err(Error::Setup(anyhow::anyhow!("{} is missing", self.name)));
+            }
+            Ok(Box::new(TestProvider {
+                fallback: FallbackProvider::new(Location::build()).with_name(self.name),
+                fail_warm_up: self.fail_warm_up,
+            }))
+        }
+    }
+
+    #[test]
+    fn metrics_name() {
+        for (name, expected) in [
+            ("maxmind", "maxmind"),
+            ("Data Center", "data_center"),
+            ("region/us-east", "region_us_east"),
+            ("Zürich", "z_rich"),
+        ] {
+            let provider = FallbackProvider::new(Location::build()).with_name(name);
+            assert_eq!(provider.metrics_name(), expected, "for {:?}", name);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn auto_detect_first_available() {
+        let missing = TestFactory::new("missing", true, false);
+        let not_ready = TestFactory::new("not-ready", false, true);
+        let available = TestFactory::new("available", false, false);
+        let unused = TestFactory::new("unused", false, false);
+
+        let provider = auto_detect_provider(
+            AutoDetectConfig::new()
+                .with_candidate(missing.clone())
+                .with_candidate(not_ready.clone())
+                .with_candidate(available.clone())
+                .with_candidate(unused.clone()),
+        )
+        .await
+        .expect("a provider should be available");
+
+        assert_eq!(provider.name(), "available");
+        assert_eq!(missing.builds.load(Ordering::SeqCst), 1);
+        assert_eq!(not_ready.builds.load(Ordering::SeqCst), 1);
+        assert_eq!(available.builds.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            unused.builds.load(Ordering::SeqCst),
+            0,
+            "later candidates should not be built"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn auto_detect_none_available() {
+        let result = auto_detect_provider(
+            AutoDetectConfig::new()
+                .with_candidate(TestFactory::new("missing", true, false))
+                .with_candidate(TestFactory::new("not-ready", false, true)),
+        )
+        .await;
+        match result {
+            Err(Error::Multiple(errors)) => {
+                let messages: Vec<_> = errors
+                    .iter()
+                    .map(|error| error.root_cause().to_string())
+                    .collect();
+                assert_eq!(
+                    messages,
+                    vec!["missing is missing", "not-ready is not ready"]
+                );
+            }
+            Err(error) => panic!("expected Error::Multiple, got {:?}", error),
+            Ok(provider) => panic!("expected an error, got {}", provider.name()),
+        }
+
+        let result = auto_detect_provider(AutoDetectConfig::new()).await;
+        assert!(matches!(result, Err(Error::Setup(_))));
+    }
+
     #[cfg(feature = "maxmind")]
     pub(crate) mod maxmind {
-        use std::path::PathBuf;
+        #[cfg(feature = "actix-web-v4")]
+        use std::net::SocketAddr;
+        use std::{
+            path::{Path, PathBuf},
+            time::Duration,
+        };
 
-        use crate::{providers::MaxMindProvider, Error, Location, Provider};
+        use crate::{
+            providers::{
+                AsyncProviderFactory, MaxMindCombinedProvider, MaxMindProvider,
+                MaxMindProviderFactory, Schema,
+            },
+            Error, Location, Provider,
+        };
+        use maxminddb::geoip2::Asn;
 
         #[cfg(not(feature = "actix-web-v4"))]
         use actix_web_3::test::TestRequest;
@@ -237,8 +1093,10 @@ pub(crate) mod tests {
             Location::build()
                 .country("US".to_string())
                 .region("WA".to_string())
+                .region_name("Washington".to_string())
                 .city("Milton".to_string())
                 .dma(819)
+                .timezone("America/Los_Angeles".to_string())
                 .provider("maxmind".to_string())
                 .finish()
                 .expect("bug when creating location")
@@ -284,6 +1142,281 @@ pub(crate) mod tests {
             assert!(matches!(location, Err(Error::Provider(_))));
         }
 
+        #[actix_rt::test]
+        async fn combined_city_only() {
+            let provider = MaxMindCombinedProvider::from_paths(Path::new(MMDB_LOC), None)
+                .expect("could not make maxmind client");
+            let location = provider
+                .lookup_by_ip(TEST_ADDR_1.parse().unwrap())
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+            assert!(matches!(
+                provider.lookup_by_ip(TEST_ADDR_2.parse().unwrap()).await,
+                Err(Error::Provider(_))
+            ));
+        }
+
+        #[actix_rt::test]
+        async fn combined_with_asn_database() {
+            // There is no ASN test database, so use the City database in its
+            // place. Its records have no ASN fields, so `isp` stays empty;
+            // `combined_merges_asn_record` covers the merge itself.
+            let provider =
+                MaxMindCombinedProvider::from_paths(Path::new(MMDB_LOC), Some(Path::new(MMDB_LOC)))
+                    .expect("could not make maxmind client");
+
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Forwarded-For", TEST_ADDR_1)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Forwarded-For", TEST_ADDR_1))
+                .to_http_request();
+
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+            let description = provider.describe();
+            assert_eq!(description.name, "maxmind-combined");
+            assert_eq!(
+                description.details.get("asn_database_path"),
+                Some(&MMDB_LOC.to_string())
+            );
+        }
+
+        fn test_asn() -> Asn<'static> {
+            Asn {
+                autonomous_system_number: Some(209),
+                autonomous_system_organization: Some("Qwest Communications Company, LLC"),
+            }
+        }
+
+        #[actix_rt::test]
+        #[allow(deprecated)]
+        async fn combined_merges_asn_record() {
+            let provider = MaxMindCombinedProvider::from_paths(Path::new(MMDB_LOC), None)
+                .expect("could not make maxmind client");
+            let city = provider.lookup_by_ip(TEST_ADDR_1.parse().unwrap()).await;
+
+            let location = provider
+                .merge(city, Some(test_asn()))
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(
+                location,
+                Location {
+                    isp: Some("Qwest Communications Company, LLC".to_string()),
+                    ..test_location()
+                }
+            );
+        }
+
+        #[actix_rt::test]
+        async fn combined_asn_only() {
+            let provider = MaxMindCombinedProvider::from_paths(Path::new(MMDB_LOC), None)
+                .expect("could not make maxmind client");
+            let expected = Location::build()
+                .isp(Some("Qwest Communications Company, LLC".to_string()))
+                .provider("maxmind-combined".to_string())
+                .finish()
+                .expect("bug when creating location");
+
+            // The address isn't in the City database at all.
+            let city = provider.lookup_by_ip(TEST_ADDR_2.parse().unwrap()).await;
+            assert!(matches!(city, Err(Error::Provider(_))));
+            let location = provider.merge(city, Some(test_asn())).unwrap();
+            assert_eq!(location, Some(expected.clone()));
+
+            // The City database has no location for the address.
+            let location = provider.merge(Ok(None), Some(test_asn())).unwrap();
+            assert_eq!(location, Some(expected));
+
+            // Without an ASN record the City result is returned unchanged.
+            assert_eq!(provider.merge(Ok(None), None).unwrap(), None);
+        }
+
+        #[test]
+        fn combined_missing_database() {
+            assert!(matches!(
+                MaxMindCombinedProvider::from_paths(Path::new("./missing.mmdb"), None),
+                Err(Error::Setup(_))
+            ));
+            assert!(matches!(
+                MaxMindCombinedProvider::from_paths(
+                    Path::new(MMDB_LOC),
+                    Some(Path::new("./missing-asn.mmdb"))
+                ),
+                Err(Error::Setup(_))
+            ));
+        }
+
+        #[actix_rt::test]
+        async fn additional_ip_header() {
+            let provider = MaxMindProvider::build(MMDB_LOC)
+                .with_additional_ip_header("x-real-ip")
+                .with_additional_ip_header("X-Client-IP")
+                .finish()
+                .expect("could not make maxmind client");
+
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Client-IP", TEST_ADDR_1)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Client-IP", TEST_ADDR_1))
+                .to_http_request();
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+
+            // The header isn't used unless it's configured.
+            let location = MaxMindProvider::from_path(Path::new(MMDB_LOC))
+                .expect("could not make maxmind client")
+                .get_location(&request)
+                .await
+                .expect("could not get location");
+            assert_eq!(location, None);
+        }
+
+        #[cfg(feature = "actix-web-v4")]
+        #[actix_rt::test]
+        async fn peer_address_fallback() {
+            let provider = MaxMindProvider::build(MMDB_LOC)
+                .with_peer_address_fallback(true)
+                .finish()
+                .expect("could not make maxmind client");
+
+            let peer_addr = SocketAddr::new(TEST_ADDR_1.parse().unwrap(), 443);
+            let request = TestRequest::default()
+                .peer_addr(peer_addr)
+                .to_http_request();
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+
+            // X-Forwarded-For still takes priority over the peer address.
+            let request = TestRequest::default()
+                .peer_addr(SocketAddr::new(TEST_ADDR_2.parse().unwrap(), 443))
+                .insert_header(("X-Forwarded-For", TEST_ADDR_1))
+                .to_http_request();
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+
+            // The peer address isn't used unless the fallback is enabled.
+            let request = TestRequest::default()
+                .peer_addr(peer_addr)
+                .to_http_request();
+            let location = MaxMindProvider::from_path(Path::new(MMDB_LOC))
+                .expect("could not make maxmind client")
+                .get_location(&request)
+                .await
+                .expect("could not get location");
+            assert_eq!(location, None);
+        }
+
+        #[cfg(feature = "actix-web-v4")]
+        #[actix_rt::test]
+        async fn multiple_forwarded_for_headers() {
+            let provider = MaxMindProvider::from_path(Path::new(MMDB_LOC))
+                .expect("could not make maxmind client");
+
+            let request = TestRequest::default()
+                .append_header(("X-Forwarded-For", TEST_ADDR_1))
+                .append_header(("X-Forwarded-For", "127.0.0.1, 10.0.0.1"))
+                .to_http_request();
+            assert_eq!(
+                request.headers().get_all("X-Forwarded-For").count(),
+                2,
+                "the request should have two headers"
+            );
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+        }
+
+        #[test]
+        fn invalid_additional_ip_header() {
+            let result = MaxMindProvider::build(MMDB_LOC)
+                .with_additional_ip_header("not a header")
+                .finish();
+            assert!(matches!(result, Err(Error::Setup(_))));
+        }
+
+        #[actix_rt::test]
+        async fn none_for_private_ips() {
+            let provider = MaxMindProvider::build(MMDB_LOC)
+                .return_none_for_private_ips(true)
+                .finish()
+                .expect("could not make maxmind client");
+
+            for addr in [
+                "127.0.0.1",
+                "::1",
+                "10.1.2.3",
+                "172.16.0.1",
+                "192.168.1.1",
+                "169.254.0.1",
+                "fe80::1",
+                "fd00::1",
+            ] {
+                let location = provider.lookup_by_ip(addr.parse().unwrap()).await;
+                assert!(matches!(location, Ok(None)), "{} gave {:?}", addr, location);
+            }
+
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Forwarded-For", TEST_ADDR_2)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Forwarded-For", TEST_ADDR_2))
+                .to_http_request();
+            let location = provider.get_location(&request).await;
+            assert!(matches!(location, Ok(None)));
+
+            // Public addresses are still looked up, and still error if unknown.
+            let location = provider
+                .lookup_by_ip(TEST_ADDR_1.parse().unwrap())
+                .await
+                .expect("could not get location");
+            assert_eq!(location, Some(test_location()));
+            let location = provider.lookup_by_ip("8.8.8.8".parse().unwrap()).await;
+            assert!(matches!(location, Err(Error::Provider(_))));
+        }
+
+        #[test]
+        fn is_private() {
+            use crate::providers::maxmind::is_private;
+
+            for addr in ["127.0.0.1", "10.0.0.1", "192.168.0.1", "169.254.1.1", "::1"] {
+                assert!(is_private(addr.parse().unwrap()), "{}", addr);
+            }
+            for addr in ["216.160.83.56", "8.8.8.8", "2001:db8::1", "::ffff:8.8.8.8"] {
+                assert!(!is_private(addr.parse().unwrap()), "{}", addr);
+            }
+            assert!(is_private("::ffff:10.0.0.1".parse().unwrap()));
+        }
+
         #[actix_rt::test]
         async fn with_proxy_ips() {
             let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
@@ -328,6 +1461,163 @@ pub(crate) mod tests {
             assert_eq!(location, test_location());
         }
 
+        #[actix_rt::test]
+        async fn enterprise_schema() {
+            // There is no Enterprise test database, but the Enterprise schema
+            // is a superset of the City schema, so it can read City databases.
+            let provider = MaxMindProvider::build(MMDB_LOC)
+                .with_schema(Schema::Enterprise)
+                .finish()
+                .expect("could not make maxmind client");
+
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Forwarded-For", TEST_ADDR_1)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Forwarded-For", TEST_ADDR_1))
+                .to_http_request();
+
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+        }
+
+        #[actix_rt::test]
+        async fn lookup_by_ip() {
+            let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
+                .expect("could not make maxmind client");
+
+            let location = provider
+                .lookup_by_ip(TEST_ADDR_1.parse().unwrap())
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+
+            let result = provider.lookup_by_ip(TEST_ADDR_2.parse().unwrap()).await;
+            assert!(matches!(result, Err(Error::Provider(_))));
+        }
+
+        #[actix_rt::test]
+        async fn region_name_outside_north_america() {
+            let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
+                .expect("could not make maxmind client");
+
+            let location = provider
+                .lookup_by_ip("81.2.69.142".parse().unwrap())
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location.country_ref(), Some("GB"));
+            assert_eq!(location.region_ref(), Some("ENG"));
+            assert_eq!(location.region_name(), Some("England"));
+        }
+
+        #[actix_rt::test]
+        async fn warm_up() {
+            let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
+                .expect("could not make maxmind client");
+            provider.warm_up().await.expect("warm up failed");
+        }
+
+        #[test]
+        fn describe() {
+            let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
+                .expect("could not make maxmind client");
+            let description = provider.describe();
+            assert_eq!(description.name, "maxmind");
+            assert_eq!(description.kind, "maxmind");
+            assert_eq!(description.details["database_path"], MMDB_LOC);
+            assert_eq!(
+                description.details["build_date"],
+                crate::providers::maxmind::format_date(
+                    provider
+                        .last_database_update()
+                        .unwrap()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs()
+                )
+            );
+        }
+
+        #[test]
+        fn database_metadata() {
+            let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
+                .expect("could not make maxmind client");
+            let metadata = provider.database_metadata();
+            assert!(
+                metadata.database_type.contains("GeoLite2"),
+                "unexpected database type {}",
+                metadata.database_type
+            );
+            assert_eq!(metadata.ip_version, 6);
+            assert_eq!(metadata.binary_format_version, (2, 0));
+            assert!(metadata.languages.contains(&"en".to_string()));
+            assert!(metadata.description.contains_key("en"));
+            assert_eq!(
+                Some(metadata.build_epoch),
+                provider
+                    .last_database_update()
+                    .and_then(|updated| updated.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|age| age.as_secs())
+            );
+        }
+
+        #[test]
+        fn format_date() {
+            use crate::providers::maxmind::format_date;
+
+            assert_eq!(format_date(0), "1970-01-01");
+            assert_eq!(format_date(951_782_400), "2000-02-29");
+            assert_eq!(format_date(1_654_041_600 + 86_399), "2022-06-01");
+        }
+
+        #[actix_rt::test]
+        async fn factory() {
+            let provider = MaxMindProviderFactory::new(MMDB_LOC)
+                .build()
+                .await
+                .expect("could not make maxmind client");
+
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Forwarded-For", TEST_ADDR_1)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Forwarded-For", TEST_ADDR_1))
+                .to_http_request();
+
+            let location = provider
+                .get_location(&request)
+                .await
+                .expect("could not get location")
+                .expect("location was none");
+            assert_eq!(location, test_location());
+        }
+
+        #[actix_rt::test]
+        async fn factory_missing_file() {
+            let result = MaxMindProviderFactory::new("./does-not-exist.mmdb")
+                .build()
+                .await;
+            assert!(matches!(result, Err(Error::Setup(_))));
+        }
+
+        #[test]
+        fn database_update_time() {
+            let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
+                .expect("could not make maxmind client");
+            assert!(provider.last_database_update().is_some());
+            assert!(provider.database_age() > Duration::ZERO);
+        }
+
         #[test]
         fn expected_info() {
             let provider = MaxMindProvider::from_path(&PathBuf::from(MMDB_LOC))
@@ -0,0 +1,156 @@
+use std::{fs::File, io::Read, net::IpAddr, path::Path};
+
+use anyhow::{anyhow, Context};
+
+use crate::{providers::CachingProvider, Error, Location, Provider};
+
+/// Loads known IP address to location mappings to pre-populate a
+/// [`CachingProvider`], such as for the address blocks of a CDN.
+///
+/// The CSV data must have a header row, followed by rows with the columns
+/// `ip,country,region,city,dma,provider`. Empty values are treated as missing.
+///
+/// ```ignore
+/// let provider = CachingProvider::new(maxmind_provider);
+/// let entries = GeoipCacheWarmer::from_csv_path(Path::new("known-ips.csv"))?;
+/// GeoipCacheWarmer::warm(&provider, entries);
+/// ```
+pub struct GeoipCacheWarmer;
+
+impl GeoipCacheWarmer {
+    /// Read the mappings from the CSV file at `path`.
+    pub fn from_csv_path(path: &Path) -> Result<Vec<(IpAddr, Location)>, Error> {
+        let file = File::open(path)
+            .with_context(|| format!("could not open {}", path.display()))
+            .map_err(Error::Setup)?;
+        Self::from_csv_reader(file)
+    }
+
+    /// Read the mappings from CSV data.
+    pub fn from_csv_reader<R: Read>(reader: R) -> Result<Vec<(IpAddr, Location)>, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        reader
+            .records()
+            .map(|record| {
+                let record = record
+                    .context("could not read CSV row")
+                    .map_err(Error::Setup)?;
+                parse_record(&record).map_err(Error::Setup)
+            })
+            .collect()
+    }
+
+    /// Insert each of `entries` into the cache of `provider`.
+    pub fn warm<P: Provider>(provider: &CachingProvider<P>, entries: Vec<(IpAddr, Location)>) {
+        for (ip, location) in entries {
+            provider.insert_raw(ip, location);
+        }
+    }
+}
+
+fn parse_record(record: &csv::StringRecord) -> Result<(IpAddr, Location), anyhow::Error> {
+    let field = |index: usize| {
+        record
+            .get(index)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string)
+    };
+
+    let ip = field(0)
+        .ok_or_else(|| anyhow!("missing IP address"))?
+        .parse()
+        .context("invalid IP address")?;
+    let dma = field(4)
+        .map(|dma| dma.parse::<u16>())
+        .transpose()
+        .context("invalid DMA")?;
+    let location = Location::build()
+        .country(field(1))
+        .region(field(2))
+        .city(field(3))
+        .dma(dma)
+        .provider(field(5).unwrap_or_else(|| "cache".to_string()))
+        .finish()
+        .map_err(|_| anyhow!("bug when creating location"))?;
+
+    Ok((ip, location))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    #[cfg(not(feature = "actix-web-v4"))]
+    use actix_web_3::test::TestRequest;
+    #[cfg(feature = "actix-web-v4")]
+    use actix_web_4::test::TestRequest;
+
+    use super::GeoipCacheWarmer;
+    use crate::{
+        providers::{testing::CountingProvider, CachingProvider},
+        Error, Provider,
+    };
+
+    const CSV: &str = "\
+ip,country,region,city,dma,provider
+192.0.2.1,US,CA,Sacramento,862,cdn
+2001:db8::1,CA,,,,
+";
+
+    #[actix_rt::test]
+    async fn warmed_locations_skip_inner_provider() {
+        let inner = CountingProvider::default();
+        let provider = CachingProvider::new(inner.clone());
+        let entries =
+            GeoipCacheWarmer::from_csv_reader(CSV.as_bytes()).expect("could not read CSV");
+        GeoipCacheWarmer::warm(&provider, entries);
+        assert_eq!(provider.len(), 2);
+
+        #[cfg(not(feature = "actix-web-v4"))]
+        let request = TestRequest::default()
+            .header("X-Forwarded-For", "192.0.2.1")
+            .to_http_request();
+        #[cfg(feature = "actix-web-v4")]
+        let request = TestRequest::default()
+            .insert_header(("X-Forwarded-For", "192.0.2.1"))
+            .to_http_request();
+        let location = provider
+            .get_location(&request)
+            .await
+            .expect("lookup failed")
+            .expect("no location");
+        assert_eq!(location.country(), "US");
+        assert_eq!(location.city(), "Sacramento");
+        assert_eq!(location.dma(), 862);
+        assert_eq!(location.provider(), "cdn");
+
+        #[cfg(not(feature = "actix-web-v4"))]
+        let request = TestRequest::default()
+            .header("X-Forwarded-For", "2001:db8::1")
+            .to_http_request();
+        #[cfg(feature = "actix-web-v4")]
+        let request = TestRequest::default()
+            .insert_header(("X-Forwarded-For", "2001:db8::1"))
+            .to_http_request();
+        let location = provider
+            .get_location(&request)
+            .await
+            .expect("lookup failed")
+            .expect("no location");
+        assert_eq!(location.country(), "CA");
+        assert_eq!(location.region_ref(), None);
+        assert_eq!(location.provider(), "cache");
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn invalid_rows_are_errors() {
+        let result = GeoipCacheWarmer::from_csv_reader(
+            "ip,country,region,city,dma,provider\nnot-an-ip,US,,,,\n".as_bytes(),
+        );
+        assert!(matches!(result, Err(Error::Setup(_))));
+    }
+}
@@ -0,0 +1,372 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    providers::{CacheStats, ProviderDescription},
+    Error, Location, Provider,
+};
+
+#[cfg(feature = "actix-web-v3")]
+use actix_web_3::HttpRequest;
+
+#[cfg(feature = "actix-web-v4")]
+use actix_web_4::HttpRequest;
+
+/// The number of locations a [`CachingProvider`] holds by default.
+const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// A provider that remembers the locations found by another provider, keyed by
+/// client IP address.
+///
+/// The client address is the one the wrapped provider looks up, from
+/// [`Provider::client_addr`]. Requests without a client address are passed
+/// through to the wrapped provider uncached. Only successful lookups that
+/// found a location are cached.
+///
+/// Client addresses usually come from the `X-Forwarded-For` header, which
+/// clients control, so the cache holds at most
+/// [`with_max_entries`](Self::with_max_entries) locations, and evicts the
+/// least recently used one to make room for another.
+pub struct CachingProvider<P: Provider> {
+    inner: P,
+    cache: Mutex<Cache>,
+    max_entries: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl<P: Provider> CachingProvider<P> {
+    /// Wrap `inner` in a cache of up to 10,000 locations.
+    pub fn new(inner: P) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(Cache::default()),
+            max_entries: DEFAULT_MAX_ENTRIES,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        }
+    }
+
+    /// Hold at most `max_entries` locations. A cache with no room doesn't
+    /// store any locations.
+    pub fn with_max_entries(mut self, max_entries: usize) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// Store `location` as the location of `ip`, replacing any cached entry.
+    /// Later lookups for `ip` will return it without consulting the wrapped
+    /// provider, unless it has been evicted.
+    pub fn insert_raw(&self, ip: IpAddr, location: Location) {
+        let evicted =
+            self.cache
+                .lock()
+                .expect("mutex was poisoned")
+                .insert(ip, location, self.max_entries);
+        self.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+
+    /// The number of cached locations.
+    pub fn len(&self) -> usize {
+        self.cache.lock().expect("mutex was poisoned").entries.len()
+    }
+
+    /// Is the cache empty?
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The cached location of `ip`, counting the hit or miss.
+    fn cached(&self, ip: IpAddr) -> Option<Location> {
+        let cached = self.cache.lock().expect("mutex was poisoned").get(ip);
+        if cached.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        cached
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: Provider> Provider for CachingProvider<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn metrics_name(&self) -> String {
+        self.inner.metrics_name()
+    }
+
+    async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
+        let ip = match self.inner.client_addr(request) {
+            Some(ip) => ip,
+            None => return self.inner.get_location(request).await,
+        };
+
+        if let Some(location) = self.cached(ip) {
+            return Ok(Some(location));
+        }
+
+        let location = self.inner.get_location(request).await?;
+        if let Some(location) = &location {
+            self.insert_raw(ip, location.clone());
+        }
+        Ok(location)
+    }
+
+    async fn get_location_for_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+        if let Some(location) = self.cached(addr) {
+            return Ok(Some(location));
+        }
+
+        let location = self.inner.get_location_for_ip(addr).await?;
+        if let Some(location) = &location {
+            self.insert_raw(addr, location.clone());
+        }
+        Ok(location)
+    }
+
+    fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+        self.inner.client_addr(request)
+    }
+
+    async fn warm_up(&self) -> Result<(), Error> {
+        self.inner.warm_up().await
+    }
+
+    fn describe(&self) -> ProviderDescription {
+        let mut description = self.inner.describe();
+        description
+            .details
+            .insert("cached_locations".to_string(), self.len().to_string());
+        description
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        Some(CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        })
+    }
+
+    fn expect_country(&self) -> bool {
+        self.inner.expect_country()
+    }
+
+    fn expect_region(&self) -> bool {
+        self.inner.expect_region()
+    }
+
+    fn expect_city(&self) -> bool {
+        self.inner.expect_city()
+    }
+}
+
+/// Cached locations, and the order they were last used in.
+#[derive(Default)]
+struct Cache {
+    /// Each location, with the tick of its last use.
+    entries: HashMap<IpAddr, (Location, u64)>,
+    /// The address last used at each tick, oldest first.
+    recency: BTreeMap<u64, IpAddr>,
+    next_tick: u64,
+}
+
+impl Cache {
+    /// The location of `ip`, marking it as the most recently used.
+    fn get(&mut self, ip: IpAddr) -> Option<Location> {
+        let (location, last_used) = self.entries.get_mut(&ip)?;
+        self.recency.remove(last_used);
+        *last_used = self.next_tick;
+        self.recency.insert(self.next_tick, ip);
+        self.next_tick += 1;
+        Some(location.clone())
+    }
+
+    /// Store `location` as the most recently used, evicting the least recently
+    /// used locations to keep at most `max_entries`. Returns the number of
+    /// locations evicted.
+    fn insert(&mut self, ip: IpAddr, location: Location, max_entries: usize) -> u64 {
+        if let Some((_, last_used)) = self.entries.remove(&ip) {
+            self.recency.remove(&last_used);
+        }
+
+        let mut evicted = 0;
+        while self.entries.len() >= max_entries {
+            match self.recency.pop_first() {
+                Some((_, oldest)) => {
+                    self.entries.remove(&oldest);
+                    evicted += 1;
+                }
+                None => break,
+            }
+        }
+
+        if max_entries > 0 {
+            self.entries.insert(ip, (location, self.next_tick));
+            self.recency.insert(self.next_tick, ip);
+            self.next_tick += 1;
+        }
+        evicted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::IpAddr, sync::atomic::Ordering};
+
+    use async_trait::async_trait;
+
+    #[cfg(not(feature = "actix-web-v4"))]
+    use actix_web_3::{test::TestRequest, HttpRequest};
+    #[cfg(feature = "actix-web-v4")]
+    use actix_web_4::{test::TestRequest, HttpRequest};
+
+    use super::CachingProvider;
+    use crate::{
+        providers::{testing::CountingProvider, CacheStats},
+        Error, Location, Provider,
+    };
+
+    #[actix_rt::test]
+    async fn caches_by_client_ip() {
+        let inner = CountingProvider::default();
+        let provider = CachingProvider::new(inner.clone());
+
+        for ip in ["192.0.2.1", "192.0.2.1", "192.0.2.2"] {
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Forwarded-For", ip)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Forwarded-For", ip))
+                .to_http_request();
+            let location = provider.get_location(&request).await.unwrap();
+            assert_eq!(location.map(|l| l.country()), Some("CA".to_string()));
+        }
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(provider.len(), 2);
+        assert_eq!(
+            provider.cache_stats(),
+            Some(CacheStats {
+                hits: 1,
+                misses: 2,
+                evictions: 0
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn evicts_least_recently_used() {
+        let inner = CountingProvider::default();
+        let provider = CachingProvider::new(inner.clone()).with_max_entries(2);
+
+        for ip in [
+            "192.0.2.1",
+            "192.0.2.2",
+            "192.0.2.1",
+            "192.0.2.3",
+            "192.0.2.2",
+        ] {
+            provider
+                .get_location_for_ip(ip.parse().unwrap())
+                .await
+                .unwrap();
+        }
+
+        // 192.0.2.2 was evicted for 192.0.2.3, and then 192.0.2.1 for it.
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 4);
+        assert_eq!(provider.len(), 2);
+        assert_eq!(
+            provider.cache_stats(),
+            Some(CacheStats {
+                hits: 1,
+                misses: 4,
+                evictions: 2
+            })
+        );
+    }
+
+    /// A provider that looks up the address in the `X-Real-IP` header.
+    struct RealIpProvider(CountingProvider);
+
+    #[async_trait(?Send)]
+    impl Provider for RealIpProvider {
+        fn name(&self) -> &str {
+            "real-ip"
+        }
+
+        async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
+            self.0.get_location(request).await
+        }
+
+        fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+            request
+                .headers()
+                .get("X-Real-IP")?
+                .to_str()
+                .ok()?
+                .parse()
+                .ok()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn keys_by_inner_client_addr() {
+        let inner = CountingProvider::default();
+        let provider = CachingProvider::new(RealIpProvider(inner.clone()));
+
+        for real_ip in ["192.0.2.1", "192.0.2.2", "192.0.2.1"] {
+            #[cfg(not(feature = "actix-web-v4"))]
+            let request = TestRequest::default()
+                .header("X-Forwarded-For", "198.51.100.1")
+                .header("X-Real-IP", real_ip)
+                .to_http_request();
+            #[cfg(feature = "actix-web-v4")]
+            let request = TestRequest::default()
+                .insert_header(("X-Forwarded-For", "198.51.100.1"))
+                .insert_header(("X-Real-IP", real_ip))
+                .to_http_request();
+            provider.get_location(&request).await.unwrap();
+        }
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(provider.len(), 2);
+        assert!(provider
+            .get_location_for_ip("198.51.100.1".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[actix_rt::test]
+    async fn caches_lookups_by_ip() {
+        let inner = CountingProvider::default();
+        let provider = CachingProvider::new(inner.clone());
+
+        for ip in ["192.0.2.1", "192.0.2.1", "192.0.2.2"] {
+            let location = provider
+                .get_location_for_ip(ip.parse().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(location.map(|l| l.country()), Some("CA".to_string()));
+        }
+
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(provider.len(), 2);
+    }
+}
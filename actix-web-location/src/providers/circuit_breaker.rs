@@ -0,0 +1,271 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::{
+    providers::{CacheStats, ProviderDescription},
+    Error, Location, Provider,
+};
+
+#[cfg(feature = "actix-web-v3")]
+use actix_web_3::HttpRequest;
+
+#[cfg(feature = "actix-web-v4")]
+use actix_web_4::HttpRequest;
+
+/// A provider that stops calling a failing provider for a while.
+///
+/// After `failure_threshold` consecutive errors from the wrapped provider, the
+/// circuit "opens" and all lookups return `Ok(None)` without calling the
+/// wrapped provider. Once `reset_timeout` has passed, a single lookup is let
+/// through as a probe. If it succeeds the circuit closes again, otherwise it
+/// stays open for another `reset_timeout`.
+pub struct CircuitBreakerProvider<P: Provider> {
+    inner: P,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    consecutive_failures: AtomicU32,
+    opened_at: Mutex<Option<Instant>>,
+}
+
+impl<P: Provider> CircuitBreakerProvider<P> {
+    /// Wrap `inner` in a circuit breaker.
+    pub fn new(inner: P, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        Self {
+            inner,
+            failure_threshold,
+            reset_timeout,
+            consecutive_failures: AtomicU32::new(0),
+            opened_at: Mutex::new(None),
+        }
+    }
+
+    /// Is the circuit currently open, meaning lookups are being skipped?
+    pub fn is_open(&self) -> bool {
+        self.opened_at.lock().expect("mutex was poisoned").is_some()
+    }
+
+    /// Decide if a lookup should be sent to the wrapped provider. If the reset
+    /// timeout has passed, this lets exactly one probe through by restarting
+    /// the timeout.
+    fn should_try(&self) -> bool {
+        let mut opened_at = self.opened_at.lock().expect("mutex was poisoned");
+        match *opened_at {
+            None => true,
+            Some(at) if at.elapsed() >= self.reset_timeout => {
+                *opened_at = Some(Instant::now());
+                true
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::SeqCst);
+        *self.opened_at.lock().expect("mutex was poisoned") = None;
+    }
+
+    fn record_failure(&self) {
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::SeqCst) + 1;
+        if failures >= self.failure_threshold {
+            *self.opened_at.lock().expect("mutex was poisoned") = Some(Instant::now());
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: Provider> Provider for CircuitBreakerProvider<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn metrics_name(&self) -> String {
+        self.inner.metrics_name()
+    }
+
+    async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
+        if !self.should_try() {
+            return Ok(None);
+        }
+
+        let result = self.inner.get_location(request).await;
+        if result.is_ok() {
+            self.record_success();
+        } else {
+            self.record_failure();
+        }
+        result
+    }
+
+    async fn get_location_for_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+        if !self.should_try() {
+            return Ok(None);
+        }
+
+        let result = self.inner.get_location_for_ip(addr).await;
+        if result.is_ok() {
+            self.record_success();
+        } else {
+            self.record_failure();
+        }
+        result
+    }
+
+    fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+        self.inner.client_addr(request)
+    }
+
+    async fn warm_up(&self) -> Result<(), Error> {
+        self.inner.warm_up().await
+    }
+
+    fn describe(&self) -> ProviderDescription {
+        let mut description = self.inner.describe();
+        description
+            .details
+            .insert("circuit_open".to_string(), self.is_open().to_string());
+        description
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn expect_country(&self) -> bool {
+        self.inner.expect_country()
+    }
+
+    fn expect_region(&self) -> bool {
+        self.inner.expect_region()
+    }
+
+    fn expect_city(&self) -> bool {
+        self.inner.expect_city()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use anyhow::anyhow;
+    use async_trait::async_trait;
+
+    #[cfg(not(feature = "actix-web-v4"))]
+    use actix_web_3::{test::TestRequest, HttpRequest};
+    #[cfg(feature = "actix-web-v4")]
+    use actix_web_4::{test::TestRequest, HttpRequest};
+
+    use super::CircuitBreakerProvider;
+    use crate::{Error, Location, Provider};
+
+    /// A provider that fails while `failing` is set, and counts its calls.
+    #[derive(Clone, Default)]
+    struct FlakyProvider {
+        failing: Arc<AtomicBool>,
+        calls: Arc<AtomicU32>,
+    }
+
+    #[async_trait(?Send)]
+    impl Provider for FlakyProvider {
+        fn name(&self) -> &str {
+            "flaky"
+        }
+
+        async fn get_location(&self, _request: &HttpRequest) -> Result<Option<Location>, Error> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                Err(Error::Provider(anyhow!("flaky provider failed")))
+            } else {
+                Ok(Some(
+                    Location::build()
+                        .provider("flaky".to_string())
+                        .finish()
+                        .expect("bug when creating location"),
+                ))
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn stays_closed_below_threshold() {
+        let inner = FlakyProvider::default();
+        inner.failing.store(true, Ordering::SeqCst);
+        let provider = CircuitBreakerProvider::new(inner.clone(), 3, Duration::from_secs(60));
+        let request = TestRequest::default().to_http_request();
+
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(!provider.is_open());
+
+        inner.failing.store(false, Ordering::SeqCst);
+        assert!(provider.get_location(&request).await.unwrap().is_some());
+
+        // The success reset the count, so two more failures don't open it.
+        inner.failing.store(true, Ordering::SeqCst);
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(!provider.is_open());
+    }
+
+    #[actix_rt::test]
+    async fn opens_at_threshold() {
+        let inner = FlakyProvider::default();
+        inner.failing.store(true, Ordering::SeqCst);
+        let provider = CircuitBreakerProvider::new(inner.clone(), 2, Duration::from_secs(60));
+        let request = TestRequest::default().to_http_request();
+
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(provider.is_open());
+
+        assert!(matches!(provider.get_location(&request).await, Ok(None)));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_rt::test]
+    async fn successful_probe_closes() {
+        let inner = FlakyProvider::default();
+        inner.failing.store(true, Ordering::SeqCst);
+        let provider = CircuitBreakerProvider::new(inner.clone(), 1, Duration::from_millis(10));
+        let request = TestRequest::default().to_http_request();
+
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(provider.is_open());
+
+        std::thread::sleep(Duration::from_millis(20));
+        inner.failing.store(false, Ordering::SeqCst);
+        assert!(provider.get_location(&request).await.unwrap().is_some());
+        assert!(!provider.is_open());
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[actix_rt::test]
+    async fn failed_probe_stays_open() {
+        let inner = FlakyProvider::default();
+        inner.failing.store(true, Ordering::SeqCst);
+        let provider = CircuitBreakerProvider::new(inner.clone(), 1, Duration::from_millis(10));
+        let request = TestRequest::default().to_http_request();
+
+        assert!(provider.get_location(&request).await.is_err());
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(provider.get_location(&request).await.is_err());
+        assert!(provider.is_open());
+        assert!(matches!(provider.get_location(&request).await, Ok(None)));
+        assert_eq!(inner.calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context};
+use flate2::read::GzDecoder;
+use reqwest::StatusCode;
+
+use crate::{providers::MaxMindProvider, Error};
+
+// Only actix-web 4 is supported, since reqwest needs a Tokio 1 runtime.
+use actix_web_4::web;
+
+/// The MaxMind GeoIP Update API.
+const DEFAULT_BASE_URL: &str = "https://updates.maxmind.com";
+
+/// The MD5 hash to send when there is no current database, so that the
+/// latest database is always downloaded.
+const MISSING_DATABASE_MD5: &str = "00000000000000000000000000000000";
+
+/// Downloads new versions of a MaxMind database with the GeoIP Update API,
+/// which requires a MaxMind account ID and license key.
+///
+/// To keep a [`MaxMindProvider`] up to date, call
+/// [`update`](Self::update) from a background task on a schedule:
+///
+/// ```ignore
+/// let updater = MaxMindUpdater::new(account_id, license_key, "GeoLite2-City");
+/// loop {
+///     if let Err(error) = updater.update(&provider).await {
+///         tracing::warn!(?error, "could not update the MaxMind database");
+///     }
+///     actix_rt::time::sleep(Duration::from_secs(24 * 60 * 60)).await;
+/// }
+/// ```
+pub struct MaxMindUpdater {
+    account_id: u32,
+    license_key: String,
+    edition: String,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl MaxMindUpdater {
+    /// Create an updater for the database `edition`, such as `"GeoLite2-City"`.
+    pub fn new(
+        account_id: u32,
+        license_key: impl Into<String>,
+        edition: impl Into<String>,
+    ) -> Self {
+        Self {
+            account_id,
+            license_key: license_key.into(),
+            edition: edition.into(),
+            base_url: DEFAULT_BASE_URL.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Use a different server than `https://updates.maxmind.com`, such as a
+    /// mirror.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
+
+    /// Download the latest database to `target_path`, unless the file there
+    /// is already the latest version. Returns whether a new database was
+    /// downloaded.
+    ///
+    /// The download is checked to be a valid database before it replaces the
+    /// existing file. Reading, decompressing and writing databases is done
+    /// without blocking the async runtime.
+    pub async fn download_latest(&self, target_path: &Path) -> Result<bool, Error> {
+        let target_path = target_path.to_path_buf();
+        let current_md5 = {
+            let target_path = target_path.clone();
+            run_blocking(move || database_md5(&target_path)).await?
+        };
+
+        let url = format!(
+            "{}/geoip/databases/{}/update",
+            self.base_url.trim_end_matches('/'),
+            self.edition
+        );
+        let response = self
+            .client
+            .get(url)
+            .query(&[("db_md5", &current_md5)])
+            .basic_auth(self.account_id, Some(&self.license_key))
+            .send()
+            .await
+            .map_err(|e| Error::Provider(e.into()))?;
+        match response.status() {
+            StatusCode::NOT_MODIFIED => return Ok(false),
+            status if status.is_success() => {}
+            status => {
+                return Err(Error::Provider(anyhow!(
+                    "MaxMind update request failed with status {}",
+                    status
+                )))
+            }
+        }
+
+        let compressed = response
+            .bytes()
+            .await
+            .map_err(|e| Error::Provider(e.into()))?;
+        run_blocking(move || install_database(&compressed, &target_path)).await?;
+        Ok(true)
+    }
+
+    /// Download the latest database to the file `provider` reads, and reload
+    /// the provider if there was a new database. Returns whether the database
+    /// was updated.
+    pub async fn update(&self, provider: &MaxMindProvider) -> Result<bool, Error> {
+        let updated = self.download_latest(provider.database_path()).await?;
+        if updated {
+            let provider = provider.clone();
+            run_blocking(move || provider.reload_file()).await?;
+        }
+        Ok(updated)
+    }
+}
+
+/// The MD5 hash of the database at `path`, as the GeoIP Update API expects.
+fn database_md5(path: &Path) -> Result<String, Error> {
+    match fs::read(path) {
+        Ok(database) => Ok(format!("{:x}", md5::compute(database))),
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(MISSING_DATABASE_MD5.to_string()),
+        Err(error) => Err(Error::Setup(
+            anyhow!(error).context(format!("could not read {}", path.display())),
+        )),
+    }
+}
+
+/// Decompress a downloaded database, check that it is valid, and replace the
+/// database at `target_path` with it.
+fn install_database(compressed: &[u8], target_path: &Path) -> Result<(), Error> {
+    let mut database = Vec::new();
+    GzDecoder::new(compressed)
+        .read_to_end(&mut database)
+        .context("could not decompress the downloaded database")
+        .map_err(Error::Provider)?;
+    maxminddb::Reader::from_source(&database[..])
+        .map_err(|e| Error::Provider(anyhow!("downloaded database is invalid: {}", e)))?;
+
+    // Write to a temporary file first, so that the database is replaced
+    // all at once.
+    let download_path = download_path(target_path);
+    fs::write(&download_path, &database)
+        .and_then(|_| fs::rename(&download_path, target_path))
+        .with_context(|| format!("could not write {}", target_path.display()))
+        .map_err(Error::Setup)
+}
+
+/// Run `f` on the blocking thread pool, since database files are large enough
+/// that reading or writing them would stall the async runtime.
+async fn run_blocking<T, F>(f: F) -> Result<T, Error>
+where
+    F: FnOnce() -> Result<T, Error> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(f)
+        .await
+        .map_err(|e| Error::Provider(anyhow!("{}", e)))?
+}
+
+/// The temporary file that a new database for `target_path` is written to.
+fn download_path(target_path: &Path) -> PathBuf {
+    let mut path = OsString::from(target_path.as_os_str());
+    path.push(".download");
+    PathBuf::from(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::Write,
+        path::{Path, PathBuf},
+    };
+
+    use flate2::{write::GzEncoder, Compression};
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{MaxMindUpdater, MISSING_DATABASE_MD5};
+    use crate::{
+        providers::{tests::maxmind::MMDB_LOC, MaxMindProvider},
+        Error,
+    };
+
+    const UPDATE_PATH: &str = "/geoip/databases/GeoLite2-City/update";
+
+    /// Base64 of `42:secret`, the account ID and license key used in tests.
+    const BASIC_AUTH: &str = "Basic NDI6c2VjcmV0";
+
+    fn test_dir(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "maxmind-updater-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).expect("could not create test directory");
+        directory
+    }
+
+    fn gzipped_database() -> Vec<u8> {
+        gzip(&fs::read(MMDB_LOC).expect("could not read test database"))
+    }
+
+    fn gzip(database: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(database).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// The test database with its build time changed to `build_epoch`, so that
+    /// it can be told apart from the original.
+    fn database_built_at(build_epoch: u32) -> Vec<u8> {
+        fn find(haystack: &[u8], needle: &[u8]) -> usize {
+            haystack
+                .windows(needle.len())
+                .position(|window| window == needle)
+                .expect("could not find the database metadata")
+        }
+
+        let mut database = fs::read(MMDB_LOC).expect("could not read test database");
+        let metadata = find(&database, b"\xab\xcd\xefMaxMind.com");
+        let value = metadata + find(&database[metadata..], b"build_epoch") + "build_epoch".len();
+        // A four byte uint64 starts with an extended type control byte.
+        assert_eq!(
+            database[value..value + 2],
+            [0x04, 0x02],
+            "unexpected build_epoch encoding"
+        );
+        database[value + 2..value + 6].copy_from_slice(&build_epoch.to_be_bytes());
+        database
+    }
+
+    fn updater(server: &MockServer) -> MaxMindUpdater {
+        MaxMindUpdater::new(42, "secret", "GeoLite2-City").with_base_url(server.uri())
+    }
+
+    #[actix_rt::test]
+    async fn downloads_missing_database() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(UPDATE_PATH))
+            .and(query_param("db_md5", MISSING_DATABASE_MD5))
+            .and(header("Authorization", BASIC_AUTH))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(gzipped_database()))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let target = test_dir("missing").join("GeoLite2-City.mmdb");
+        let _ = fs::remove_file(&target);
+        let updated = updater(&server)
+            .download_latest(&target)
+            .await
+            .expect("could not download database");
+
+        assert!(updated);
+        assert_eq!(fs::read(&target).unwrap(), fs::read(MMDB_LOC).unwrap());
+        fs::remove_file(&target).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn current_database_is_not_downloaded() {
+        let current_md5 = format!("{:x}", md5::compute(fs::read(MMDB_LOC).unwrap()));
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(UPDATE_PATH))
+            .and(query_param("db_md5", current_md5.as_str()))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let provider =
+            MaxMindProvider::from_path(Path::new(MMDB_LOC)).expect("could not make maxmind client");
+        let updated = updater(&server)
+            .update(&provider)
+            .await
+            .expect("could not check for updates");
+        assert!(!updated);
+    }
+
+    #[actix_rt::test]
+    async fn update_reloads_provider() {
+        let build_epoch = 1_700_000_000;
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(UPDATE_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_bytes(gzip(&database_built_at(build_epoch))),
+            )
+            .mount(&server)
+            .await;
+
+        let target = test_dir("reload").join("GeoLite2-City.mmdb");
+        fs::copy(MMDB_LOC, &target).unwrap();
+        let provider = MaxMindProvider::from_path(&target).expect("could not make maxmind client");
+        assert_ne!(
+            provider.database_metadata().build_epoch,
+            u64::from(build_epoch)
+        );
+
+        let updated = updater(&server)
+            .update(&provider)
+            .await
+            .expect("could not update database");
+        assert!(updated);
+        assert_eq!(
+            provider.database_metadata().build_epoch,
+            u64::from(build_epoch),
+            "the provider should use the downloaded database"
+        );
+        assert!(provider
+            .lookup_by_ip("216.160.83.56".parse().unwrap())
+            .await
+            .unwrap()
+            .is_some());
+        fs::remove_file(&target).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn errors_are_reported() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        let target = test_dir("unauthorized").join("GeoLite2-City.mmdb");
+        assert!(matches!(
+            updater(&server).download_latest(&target).await,
+            Err(Error::Provider(_))
+        ));
+
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"not a database".to_vec()))
+            .mount(&server)
+            .await;
+        fs::write(&target, b"old database").unwrap();
+        assert!(matches!(
+            updater(&server).download_latest(&target).await,
+            Err(Error::Provider(_))
+        ));
+        assert_eq!(
+            fs::read(&target).unwrap(),
+            b"old database",
+            "a bad download should not replace the database"
+        );
+        fs::remove_file(&target).unwrap();
+    }
+}
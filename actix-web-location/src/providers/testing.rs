@@ -0,0 +1,77 @@
+//! Providers with fixed behavior, shared by the tests of this crate.
+
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+
+#[cfg(not(feature = "actix-web-v4"))]
+use actix_web_3::{test::TestRequest, HttpRequest};
+#[cfg(feature = "actix-web-v4")]
+use actix_web_4::{test::TestRequest, HttpRequest};
+
+use crate::{Error, Location, Provider};
+
+/// A provider that always finds the same location, and counts its calls.
+#[derive(Clone, Default)]
+pub(crate) struct CountingProvider {
+    pub(crate) calls: Arc<AtomicU32>,
+}
+
+#[async_trait(?Send)]
+impl Provider for CountingProvider {
+    fn name(&self) -> &str {
+        "counting"
+    }
+
+    async fn get_location(&self, _request: &HttpRequest) -> Result<Option<Location>, Error> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(
+            Location::build()
+                .country("CA".to_string())
+                .provider("counting".to_string())
+                .finish()
+                .expect("bug when creating location"),
+        ))
+    }
+
+    async fn get_location_for_ip(&self, _addr: IpAddr) -> Result<Option<Location>, Error> {
+        self.get_location(&TestRequest::default().to_http_request())
+            .await
+    }
+}
+
+/// A provider that never finds a location.
+pub(crate) struct EmptyProvider;
+
+#[async_trait(?Send)]
+impl Provider for EmptyProvider {
+    fn name(&self) -> &str {
+        "empty"
+    }
+
+    async fn get_location(&self, _request: &HttpRequest) -> Result<Option<Location>, Error> {
+        Ok(None)
+    }
+}
+
+/// A provider named by its field, that always fails.
+#[derive(Clone)]
+pub(crate) struct FailingProvider(pub(crate) &'static str);
+
+#[async_trait(?Send)]
+impl Provider for FailingProvider {
+    fn name(&self) -> &str {
+        self.0
+    }
+
+    async fn get_location(&self, _request: &HttpRequest) -> Result<Option<Location>, Error> {
+        Err(Error::Provider(anyhow!("{} is down", self.0)))
+    }
+}
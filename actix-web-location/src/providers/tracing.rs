@@ -0,0 +1,224 @@
+use std::{future::Future, net::IpAddr, time::Instant};
+
+use ::tracing::{field, info_span, Instrument};
+use async_trait::async_trait;
+
+use crate::{
+    providers::{CacheStats, ProviderDescription},
+    Error, Location, Provider,
+};
+
+#[cfg(feature = "actix-web-v3")]
+use actix_web_3::HttpRequest;
+
+#[cfg(feature = "actix-web-v4")]
+use actix_web_4::HttpRequest;
+
+/// A provider that runs each lookup of another provider in a
+/// `location_lookup` tracing span, so provider calls appear in traces.
+///
+/// The span has the fields `provider`, the name of the wrapped provider,
+/// `outcome`, one of `"hit"`, `"miss"`, or `"error"`, and `elapsed_ms`, the
+/// duration of the lookup in milliseconds.
+pub struct TracingProvider<P: Provider> {
+    inner: P,
+}
+
+impl<P: Provider> TracingProvider<P> {
+    /// Wrap `inner` so its lookups are traced.
+    pub fn new(inner: P) -> Self {
+        Self { inner }
+    }
+}
+
+impl<P: Provider> TracingProvider<P> {
+    /// Run `lookup` in a `location_lookup` span, recording its outcome.
+    async fn traced(
+        &self,
+        lookup: impl Future<Output = Result<Option<Location>, Error>>,
+    ) -> Result<Option<Location>, Error> {
+        let span = info_span!(
+            "location_lookup",
+            provider = self.inner.name(),
+            outcome = field::Empty,
+            elapsed_ms = field::Empty,
+        );
+
+        let start = Instant::now();
+        let result = lookup.instrument(span.clone()).await;
+
+        span.record(
+            "outcome",
+            match &result {
+                Ok(Some(_)) => "hit",
+                Ok(None) => "miss",
+                Err(_) => "error",
+            },
+        );
+        span.record("elapsed_ms", start.elapsed().as_millis() as u64);
+        result
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: Provider> Provider for TracingProvider<P> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn metrics_name(&self) -> String {
+        self.inner.metrics_name()
+    }
+
+    async fn get_location(&self, request: &HttpRequest) -> Result<Option<Location>, Error> {
+        self.traced(self.inner.get_location(request)).await
+    }
+
+    async fn get_location_for_ip(&self, addr: IpAddr) -> Result<Option<Location>, Error> {
+        self.traced(self.inner.get_location_for_ip(addr)).await
+    }
+
+    fn client_addr(&self, request: &HttpRequest) -> Option<IpAddr> {
+        self.inner.client_addr(request)
+    }
+
+    async fn warm_up(&self) -> Result<(), Error> {
+        self.inner.warm_up().await
+    }
+
+    fn describe(&self) -> ProviderDescription {
+        self.inner.describe()
+    }
+
+    fn cache_stats(&self) -> Option<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn expect_country(&self) -> bool {
+        self.inner.expect_country()
+    }
+
+    fn expect_region(&self) -> bool {
+        self.inner.expect_region()
+    }
+
+    fn expect_city(&self) -> bool {
+        self.inner.expect_city()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+    };
+
+    use serde_json::{json, Value};
+    use tracing::{
+        field::{Field, Visit},
+        span, Subscriber,
+    };
+    use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer};
+
+    #[cfg(not(feature = "actix-web-v4"))]
+    use actix_web_3::test::TestRequest;
+    #[cfg(feature = "actix-web-v4")]
+    use actix_web_4::test::TestRequest;
+
+    use super::TracingProvider;
+    use crate::{
+        providers::{
+            testing::{EmptyProvider, FailingProvider},
+            FallbackProvider,
+        },
+        Location, Provider,
+    };
+
+    type SpanFields = HashMap<&'static str, Value>;
+
+    /// Collects the fields of each span when it closes.
+    #[derive(Clone, Default)]
+    struct SpanRecorder(Arc<Mutex<Vec<(&'static str, SpanFields)>>>);
+
+    struct FieldVisitor<'a>(&'a mut SpanFields);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.insert(field.name(), json!(value));
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.insert(field.name(), json!(value));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name(), json!(format!("{:?}", value)));
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanRecorder {
+        fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+            let mut fields = SpanFields::new();
+            attrs.record(&mut FieldVisitor(&mut fields));
+            ctx.span(id).unwrap().extensions_mut().insert(fields);
+        }
+
+        fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            values.record(&mut FieldVisitor(
+                extensions.get_mut::<SpanFields>().unwrap(),
+            ));
+        }
+
+        fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+            let span = ctx.span(&id).unwrap();
+            let fields = span.extensions_mut().remove::<SpanFields>().unwrap();
+            self.0.lock().unwrap().push((span.name(), fields));
+        }
+    }
+
+    /// Run `provider` once, and return the fields of the `location_lookup`
+    /// span.
+    async fn trace_lookup<P: Provider>(provider: TracingProvider<P>) -> SpanFields {
+        let recorder = SpanRecorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let request = TestRequest::default().to_http_request();
+        let _ = provider.get_location(&request).await;
+
+        let mut spans = recorder.0.lock().unwrap();
+        assert_eq!(spans.len(), 1);
+        let (name, fields) = spans.remove(0);
+        assert_eq!(name, "location_lookup");
+        fields
+    }
+
+    #[actix_rt::test]
+    async fn records_hits() {
+        let fields = trace_lookup(TracingProvider::new(FallbackProvider::new(
+            Location::build().country("CA".to_string()),
+        )))
+        .await;
+        assert_eq!(fields["provider"], json!("fallback"));
+        assert_eq!(fields["outcome"], json!("hit"));
+        assert!(fields["elapsed_ms"].is_u64(), "{:?}", fields);
+    }
+
+    #[actix_rt::test]
+    async fn records_misses() {
+        let fields = trace_lookup(TracingProvider::new(EmptyProvider)).await;
+        assert_eq!(fields["provider"], json!("empty"));
+        assert_eq!(fields["outcome"], json!("miss"));
+        assert!(fields["elapsed_ms"].is_u64(), "{:?}", fields);
+    }
+
+    #[actix_rt::test]
+    async fn records_errors() {
+        let fields = trace_lookup(TracingProvider::new(FailingProvider("failing"))).await;
+        assert_eq!(fields["provider"], json!("failing"));
+        assert_eq!(fields["outcome"], json!("error"));
+    }
+}
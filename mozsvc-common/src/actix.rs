@@ -0,0 +1,188 @@
+//! Actix Web middleware for exposing build metadata, and handlers for
+//! heartbeat endpoints.
+
+use std::{
+    fmt,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderName, HeaderValue},
+    web, HttpResponse,
+};
+use serde_json::json;
+
+use crate::{
+    health::{CompositeHealthCheck, HealthCheck},
+    BuildInfo,
+};
+
+/// Errors that can occur while setting up [`VersionHeadersMiddleware`].
+#[derive(Debug)]
+pub enum Error {
+    /// Neither `VERSION` nor `SOURCE_VERSION` were set in the environment.
+    MissingBuildInfo,
+    /// A value of the build information can't be used as a header value.
+    InvalidHeaderValue(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingBuildInfo => write!(f, "no build information in the environment"),
+            Error::InvalidHeaderValue(name) => {
+                write!(f, "build information is not a valid value for {}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Middleware that adds the service's build information to every response.
+///
+/// The headers added are `X-Backend-Version` with the version, `X-Git-Commit`
+/// with the commit, and `X-Source-Version` with the source. Headers for empty
+/// values are omitted.
+///
+/// ```no_run
+/// use actix_web::{App, HttpServer};
+/// use mozsvc_common::actix::VersionHeadersMiddleware;
+///
+/// let version_headers = VersionHeadersMiddleware::new().expect("no build information");
+///
+/// let server = HttpServer::new(move || App::new().wrap(version_headers.clone()));
+/// ```
+#[derive(Clone)]
+pub struct VersionHeadersMiddleware {
+    headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl VersionHeadersMiddleware {
+    /// Create the middleware using the build information from
+    /// [`BuildInfo::from_env`].
+    pub fn new() -> Result<Self, Error> {
+        Self::from_build_info(&BuildInfo::from_env().ok_or(Error::MissingBuildInfo)?)
+    }
+
+    /// Create the middleware using the given build information.
+    pub fn from_build_info(info: &BuildInfo) -> Result<Self, Error> {
+        let mut headers = Vec::new();
+        for (name, value) in [
+            ("x-backend-version", &info.version),
+            ("x-git-commit", &info.commit),
+            ("x-source-version", &info.source),
+        ] {
+            if value.is_empty() {
+                continue;
+            }
+            let value =
+                HeaderValue::from_str(value).map_err(|_| Error::InvalidHeaderValue(name))?;
+            headers.push((HeaderName::from_static(name), value));
+        }
+        Ok(Self {
+            headers: Arc::new(headers),
+        })
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for VersionHeadersMiddleware
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Transform = VersionHeadersService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(VersionHeadersService {
+            service,
+            headers: self.headers.clone(),
+        }))
+    }
+}
+
+type ServiceFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>>>>;
+
+/// The service created by [`VersionHeadersMiddleware`].
+pub struct VersionHeadersService<S> {
+    service: S,
+    headers: Arc<Vec<(HeaderName, HeaderValue)>>,
+}
+
+impl<S, B> Service<ServiceRequest> for VersionHeadersService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = ServiceFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let fut = self.service.call(req);
+        let headers = self.headers.clone();
+
+        Box::pin(async move {
+            let mut response = fut.await?;
+            for (name, value) in headers.iter() {
+                response.headers_mut().insert(name.clone(), value.clone());
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Handler for the `/__heartbeat__` endpoint, which reports whether the
+/// service and its dependencies are healthy.
+///
+/// Runs the [`CompositeHealthCheck`] registered as app data. Responds with
+/// `200 OK` and `{"status": "ok"}` if it passes, or `503 Service Unavailable`
+/// and `{"status": "error", "error": "..."}` if it fails.
+///
+/// ```no_run
+/// use actix_web::{web, App, HttpServer};
+/// use mozsvc_common::{actix::heartbeat_handler, health::CompositeHealthCheck};
+///
+/// let checks = web::Data::new(CompositeHealthCheck::new());
+///
+/// let server = HttpServer::new(move || {
+///     App::new()
+///         .app_data(checks.clone())
+///         .route("/__heartbeat__", web::get().to(heartbeat_handler))
+/// });
+/// ```
+pub async fn heartbeat_handler(checks: web::Data<CompositeHealthCheck>) -> HttpResponse {
+    match checks.check().await {
+        Ok(()) => HttpResponse::Ok().json(json!({ "status": "ok" })),
+        Err(error) => {
+            HttpResponse::ServiceUnavailable().json(json!({ "status": "error", "error": error }))
+        }
+    }
+}
+
+/// Handler for the `/__lbheartbeat__` endpoint, which load balancers use to
+/// decide whether to send traffic to the service.
+///
+/// Runs the [`CompositeHealthCheck`] registered as app data, like
+/// [`heartbeat_handler`], but responds with an empty body: `200 OK` if it
+/// passes, or `503 Service Unavailable` if it fails.
+pub async fn lbheartbeat_handler(checks: web::Data<CompositeHealthCheck>) -> HttpResponse {
+    match checks.check().await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
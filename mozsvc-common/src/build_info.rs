@@ -0,0 +1,114 @@
+use std::env;
+
+use serde::Deserialize;
+
+/// Information about the build of the running service.
+///
+/// Mozilla services are deployed with a `VERSION` environment variable
+/// holding a JSON object with `version`, `commit`, and `source` keys. Some
+/// deployment platforms instead provide the commit via `SOURCE_VERSION`.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// The released version of the service, such as "1.2.3".
+    #[serde(default)]
+    pub version: String,
+    /// The VCS commit the service was built from.
+    #[serde(default)]
+    pub commit: String,
+    /// The location of the service's source, usually a repository URL.
+    #[serde(default)]
+    pub source: String,
+}
+
+impl BuildInfo {
+    /// Read the build information from the `VERSION` and `SOURCE_VERSION`
+    /// environment variables.
+    ///
+    /// `SOURCE_VERSION` is used for the commit if `VERSION` doesn't include
+    /// one. Returns `None` if neither variable is set, or if `VERSION` isn't
+    /// valid JSON.
+    pub fn from_env() -> Option<Self> {
+        let source_version = env::var("SOURCE_VERSION").ok();
+        let mut info = match env::var("VERSION") {
+            Ok(version) => serde_json::from_str::<Self>(&version).ok()?,
+            Err(_) => Self {
+                commit: source_version.clone()?,
+                ..Self::default()
+            },
+        };
+        if info.commit.is_empty() {
+            if let Some(commit) = source_version {
+                info.commit = commit;
+            }
+        }
+        Some(info)
+    }
+
+    /// Record the build information on the current span.
+    ///
+    /// The span must declare `version`, `commit`, and `source` fields, for
+    /// example with `tracing::field::Empty`, or they will be ignored.
+    pub fn inject_as_tracing_fields(&self) {
+        let span = tracing::Span::current();
+        span.record("version", self.version.as_str());
+        span.record("commit", self.commit.as_str());
+        span.record("source", self.source.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::BuildInfo;
+
+    /// The environment is shared between tests, so all cases are run from a
+    /// single test.
+    #[test]
+    fn from_env() {
+        env::remove_var("VERSION");
+        env::remove_var("SOURCE_VERSION");
+        assert_eq!(BuildInfo::from_env(), None);
+
+        env::set_var(
+            "VERSION",
+            r#"{"version": "1.2.3", "commit": "abc123", "source": "https://github.com/mozilla-services/example"}"#,
+        );
+        assert_eq!(
+            BuildInfo::from_env(),
+            Some(BuildInfo {
+                version: "1.2.3".to_string(),
+                commit: "abc123".to_string(),
+                source: "https://github.com/mozilla-services/example".to_string(),
+            })
+        );
+
+        env::set_var("SOURCE_VERSION", "def456");
+        assert_eq!(BuildInfo::from_env().unwrap().commit, "abc123");
+
+        env::set_var("VERSION", r#"{"version": "1.2.3"}"#);
+        assert_eq!(
+            BuildInfo::from_env(),
+            Some(BuildInfo {
+                version: "1.2.3".to_string(),
+                commit: "def456".to_string(),
+                source: String::new(),
+            })
+        );
+
+        env::remove_var("VERSION");
+        assert_eq!(
+            BuildInfo::from_env(),
+            Some(BuildInfo {
+                commit: "def456".to_string(),
+                ..BuildInfo::default()
+            })
+        );
+
+        env::set_var("VERSION", "not json");
+        assert_eq!(BuildInfo::from_env(), None);
+
+        env::remove_var("VERSION");
+        env::remove_var("SOURCE_VERSION");
+    }
+}
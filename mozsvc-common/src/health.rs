@@ -0,0 +1,79 @@
+//! Health checks for the heartbeat endpoints of Mozilla services.
+
+use async_trait::async_trait;
+use futures_util::future::join_all;
+
+/// The result of a health check, with a description of the problem if the
+/// check failed.
+pub type HealthStatus = Result<(), String>;
+
+/// A check of one part of a service's health, such as its connection to a
+/// database.
+///
+/// ```
+/// use async_trait::async_trait;
+/// use mozsvc_common::health::{HealthCheck, HealthStatus};
+///
+/// struct AlwaysHealthy;
+///
+/// #[async_trait]
+/// impl HealthCheck for AlwaysHealthy {
+///     async fn check(&self) -> HealthStatus {
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Check the health of this part of the service.
+    async fn check(&self) -> HealthStatus;
+}
+
+/// A health check that passes only if all of its checks pass.
+///
+/// The checks are run concurrently. If any fail, the descriptions of their
+/// problems are joined with `"; "`, in the order the checks were added.
+#[derive(Default)]
+pub struct CompositeHealthCheck {
+    checks: Vec<Box<dyn HealthCheck>>,
+}
+
+impl CompositeHealthCheck {
+    /// Create a composite health check with no checks, which always passes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a check that must pass for the composite check to pass.
+    pub fn with_check(mut self, check: impl HealthCheck + 'static) -> Self {
+        self.checks.push(Box::new(check));
+        self
+    }
+
+    /// The number of checks this composite check runs.
+    pub fn len(&self) -> usize {
+        self.checks.len()
+    }
+
+    /// Whether this composite check runs no checks.
+    pub fn is_empty(&self) -> bool {
+        self.checks.is_empty()
+    }
+}
+
+#[async_trait]
+impl HealthCheck for CompositeHealthCheck {
+    async fn check(&self) -> HealthStatus {
+        let errors: Vec<String> = join_all(self.checks.iter().map(|check| check.check()))
+            .await
+            .into_iter()
+            .filter_map(Result::err)
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
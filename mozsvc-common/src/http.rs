@@ -0,0 +1,24 @@
+//! Outbound HTTP clients.
+
+use std::time::Duration;
+
+/// How long outbound requests may take, including connecting and reading the
+/// response body.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Build an HTTP client with the defaults Mozilla services use for outbound
+/// requests.
+///
+/// Requests time out after 30 seconds, and send a `User-Agent` header of
+/// `service_name/version`, such as `"autopush/1.2.3"`, so the services they
+/// call can tell where traffic comes from. TLS certificates are always
+/// verified.
+///
+/// Proxies are read from the `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY`
+/// environment variables, if they are set.
+pub fn build_http_client(service_name: &str, version: &str) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(DEFAULT_TIMEOUT)
+        .user_agent(format!("{}/{}", service_name, version))
+        .build()
+}
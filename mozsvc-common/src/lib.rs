@@ -3,6 +3,14 @@ extern crate lazy_static;
 extern crate hostname;
 extern crate reqwest;
 
+#[cfg(feature = "actix-web")]
+pub mod actix;
 pub mod aws;
+mod build_info;
+pub mod health;
+pub mod http;
+#[cfg(feature = "sentry")]
+pub mod sentry;
 
+pub use build_info::BuildInfo;
 pub use hostname::get as get_hostname;
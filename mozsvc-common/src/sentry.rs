@@ -0,0 +1,126 @@
+//! Sentry error reporting set up from the environment.
+
+use std::env;
+
+use ::sentry::{protocol::Map, types::Dsn, Breadcrumb, ClientInitGuard, ClientOptions};
+use serde_json::Value;
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::layer::{Context, Layer};
+
+use crate::BuildInfo;
+
+/// Initialize the Sentry SDK with the DSN in the environment variable
+/// `dsn_env_var`, such as `SENTRY_DSN`.
+///
+/// The release is the version from [`BuildInfo::from_env`], if there is one.
+/// Returns `None` without initializing Sentry if the variable is unset,
+/// empty, or not a valid DSN. The returned guard must be kept alive for as
+/// long as errors should be reported.
+pub fn init_sentry(dsn_env_var: &str) -> Option<ClientInitGuard> {
+    let dsn = env::var(dsn_env_var).ok().filter(|dsn| !dsn.is_empty())?;
+    let dsn = dsn.parse::<Dsn>().ok()?;
+    Some(::sentry::init(client_options(dsn, BuildInfo::from_env())))
+}
+
+/// The Sentry options for reporting to `dsn`, with the release taken from
+/// `build_info`.
+fn client_options(dsn: Dsn, build_info: Option<BuildInfo>) -> ClientOptions {
+    let release = build_info
+        .map(|info| info.version)
+        .filter(|version| !version.is_empty());
+    ClientOptions {
+        dsn: Some(dsn),
+        release: release.map(Into::into),
+        ..ClientOptions::default()
+    }
+}
+
+/// A [`Layer`] that adds `ERROR` level tracing events as breadcrumbs to the
+/// current Sentry scope, so they are included with the next reported error.
+///
+/// The `message` field of the event becomes the breadcrumb's message, and
+/// the other fields are included as its data.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MozLogSentryLayer;
+
+impl<S: Subscriber> Layer<S> for MozLogSentryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() != Level::ERROR {
+            return;
+        }
+
+        let mut visitor = BreadcrumbVisitor::default();
+        event.record(&mut visitor);
+        ::sentry::add_breadcrumb(Breadcrumb {
+            category: Some(metadata.target().to_string()),
+            message: visitor.message,
+            level: ::sentry::Level::Error,
+            data: visitor.data,
+            ..Breadcrumb::default()
+        });
+    }
+}
+
+/// Collects the fields of an event for a breadcrumb.
+#[derive(Default)]
+struct BreadcrumbVisitor {
+    message: Option<String>,
+    data: Map<String, Value>,
+}
+
+impl Visit for BreadcrumbVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.data.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.data.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.data.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.data.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.data.insert(field.name().to_string(), value.into());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ::sentry::types::Dsn;
+
+    use super::client_options;
+    use crate::BuildInfo;
+
+    #[test]
+    fn release_from_build_info() {
+        let build_info = BuildInfo {
+            version: "1.2.3".to_string(),
+            ..BuildInfo::default()
+        };
+        let dsn: Dsn = "https://public@sentry.example.com/1".parse().unwrap();
+        let options = client_options(dsn.clone(), Some(build_info));
+        assert_eq!(options.dsn, Some(dsn.clone()));
+        assert_eq!(options.release.as_deref(), Some("1.2.3"));
+
+        let options = client_options(dsn, Some(BuildInfo::default()));
+        assert_eq!(options.release, None);
+    }
+}
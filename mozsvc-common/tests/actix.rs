@@ -0,0 +1,107 @@
+#![cfg(feature = "actix-web")]
+
+use actix_web::{http::StatusCode, test, web, App, HttpResponse};
+use async_trait::async_trait;
+use mozsvc_common::{
+    actix::{heartbeat_handler, lbheartbeat_handler, VersionHeadersMiddleware},
+    health::{CompositeHealthCheck, HealthCheck, HealthStatus},
+};
+use serde_json::json;
+
+#[actix_rt::test]
+async fn version_headers() {
+    std::env::set_var(
+        "VERSION",
+        r#"{"version": "1.2.3", "commit": "abc123", "source": "https://github.com/mozilla-services/example"}"#,
+    );
+    let middleware = VersionHeadersMiddleware::new().expect("could not create middleware");
+
+    let app = test::init_service(
+        App::new()
+            .wrap(middleware)
+            .route("/", web::get().to(HttpResponse::Ok)),
+    )
+    .await;
+    let response = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+
+    assert!(response.status().is_success());
+    let headers = response.headers();
+    assert_eq!(headers.get("X-Backend-Version").unwrap(), "1.2.3");
+    assert_eq!(headers.get("X-Git-Commit").unwrap(), "abc123");
+    assert_eq!(
+        headers.get("X-Source-Version").unwrap(),
+        "https://github.com/mozilla-services/example"
+    );
+}
+
+struct StaticCheck(HealthStatus);
+
+#[async_trait]
+impl HealthCheck for StaticCheck {
+    async fn check(&self) -> HealthStatus {
+        self.0.clone()
+    }
+}
+
+#[actix_rt::test]
+async fn heartbeat_healthy() {
+    let checks = CompositeHealthCheck::new()
+        .with_check(StaticCheck(Ok(())))
+        .with_check(StaticCheck(Ok(())));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(checks))
+            .route("/__heartbeat__", web::get().to(heartbeat_handler))
+            .route("/__lbheartbeat__", web::get().to(lbheartbeat_handler)),
+    )
+    .await;
+
+    let request = test::TestRequest::get().uri("/__heartbeat__").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!(body, json!({ "status": "ok" }));
+
+    let request = test::TestRequest::get()
+        .uri("/__lbheartbeat__")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_rt::test]
+async fn heartbeat_unhealthy() {
+    let checks = CompositeHealthCheck::new()
+        .with_check(StaticCheck(Err("database unreachable".to_string())))
+        .with_check(StaticCheck(Ok(())))
+        .with_check(StaticCheck(Err("cache unreachable".to_string())));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(checks))
+            .route("/__heartbeat__", web::get().to(heartbeat_handler))
+            .route("/__lbheartbeat__", web::get().to(lbheartbeat_handler)),
+    )
+    .await;
+
+    let request = test::TestRequest::get().uri("/__heartbeat__").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!(
+        body,
+        json!({ "status": "error", "error": "database unreachable; cache unreachable" })
+    );
+
+    let request = test::TestRequest::get()
+        .uri("/__lbheartbeat__")
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[actix_rt::test]
+async fn empty_composite_check_passes() {
+    let checks = CompositeHealthCheck::new();
+    assert!(checks.is_empty());
+    assert_eq!(checks.check().await, Ok(()));
+}
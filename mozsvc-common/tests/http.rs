@@ -0,0 +1,25 @@
+use mozsvc_common::http::build_http_client;
+use wiremock::{
+    matchers::{header, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
+
+#[actix_rt::test]
+async fn http_client_user_agent() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/"))
+        .and(header("user-agent", "test-service/1.2.3"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = build_http_client("test-service", "1.2.3").expect("could not build client");
+    let response = client
+        .get(server.uri())
+        .send()
+        .await
+        .expect("request failed");
+    assert_eq!(response.status(), 200);
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "sentry")]
+
+use mozsvc_common::sentry::{init_sentry, MozLogSentryLayer};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[test]
+fn init_without_dsn() {
+    assert!(init_sentry("MOZSVC_TEST_UNSET_SENTRY_DSN").is_none());
+}
+
+#[test]
+fn init_with_invalid_dsn() {
+    std::env::set_var("MOZSVC_TEST_INVALID_SENTRY_DSN", "not a dsn");
+    let guard = init_sentry("MOZSVC_TEST_INVALID_SENTRY_DSN");
+    std::env::remove_var("MOZSVC_TEST_INVALID_SENTRY_DSN");
+
+    assert!(guard.is_none());
+}
+
+#[test]
+fn init_with_dsn() {
+    // `VERSION` is only read by this test in this binary.
+    std::env::set_var(
+        "MOZSVC_TEST_SENTRY_DSN",
+        "https://public@sentry.example.com/1",
+    );
+    std::env::set_var("VERSION", r#"{"version": "1.2.3"}"#);
+
+    let guard = init_sentry("MOZSVC_TEST_SENTRY_DSN").expect("sentry should be initialized");
+
+    std::env::remove_var("MOZSVC_TEST_SENTRY_DSN");
+    std::env::remove_var("VERSION");
+
+    assert!(guard.is_enabled());
+    let options = guard.options();
+    let dsn = options.dsn.as_ref().expect("the DSN should be set");
+    assert_eq!(dsn.host(), "sentry.example.com");
+    assert_eq!(dsn.public_key(), "public");
+    assert_eq!(options.release.as_deref(), Some("1.2.3"));
+}
+
+#[test]
+fn error_events_become_breadcrumbs() {
+    let subscriber = tracing_subscriber::registry().with(MozLogSentryLayer);
+    let events = tracing::subscriber::with_default(subscriber, || {
+        sentry::test::with_captured_events(|| {
+            tracing::info!("not an error");
+            tracing::error!(errno = 102, "database unavailable");
+            sentry::capture_message("request failed", sentry::Level::Error);
+        })
+    });
+
+    assert_eq!(events.len(), 1);
+    let breadcrumbs = &events[0].breadcrumbs.values;
+    assert_eq!(breadcrumbs.len(), 1);
+    assert_eq!(
+        breadcrumbs[0].message.as_deref(),
+        Some("database unavailable")
+    );
+    assert_eq!(breadcrumbs[0].level, sentry::Level::Error);
+    assert_eq!(breadcrumbs[0].data.get("errno"), Some(&102.into()));
+    assert_eq!(breadcrumbs[0].category.as_deref(), Some(module_path!()));
+}
@@ -0,0 +1,76 @@
+//! Measure the overhead of the `MozLog` middleware, by comparing 1,000
+//! request/response cycles through a handler with and without it.
+
+use std::{fs::File, io::BufWriter, sync::Mutex};
+
+use actix_web::{test, web, App, HttpResponse};
+use criterion::{criterion_group, criterion_main, Criterion};
+use tracing::Dispatch;
+use tracing_actix_web_mozlog::{JsonStorageLayer, MozLog, MozLogFormatLayer};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Registry};
+
+const REQUESTS: usize = 1_000;
+
+async fn handler() -> HttpResponse {
+    HttpResponse::Ok().body("ok")
+}
+
+/// Benchmark `REQUESTS` requests to `app`, which is created on the benchmark's
+/// actix runtime.
+macro_rules! bench_requests {
+    ($c: expr, $name: expr, $app: expr) => {{
+        let system = actix_rt::System::new();
+        let app = system.block_on(async { test::init_service($app).await });
+        $c.bench_function($name, |b| {
+            b.iter(|| {
+                system.block_on(async {
+                    for _ in 0..REQUESTS {
+                        let req = test::TestRequest::with_uri("/").to_request();
+                        let res = test::call_service(&app, req).await;
+                        assert!(res.status().is_success());
+                    }
+                })
+            })
+        });
+    }};
+}
+
+/// Create a `MozLog` middleware that formats events with `make_writer`.
+fn moz_log<W: for<'a> MakeWriter<'a> + Send + Sync + 'static>(make_writer: W) -> MozLog {
+    let subscriber = Registry::default()
+        .with(JsonStorageLayer)
+        .with(MozLogFormatLayer::new("bench", make_writer));
+    tracing::dispatcher::with_default(&Dispatch::new(subscriber), MozLog::default)
+}
+
+fn no_middleware(c: &mut Criterion) {
+    bench_requests!(
+        c,
+        "no middleware",
+        App::new().route("/", web::get().to(handler))
+    );
+}
+
+fn mozlog_to_sink(c: &mut Criterion) {
+    let moz_log = moz_log(std::io::sink);
+    bench_requests!(
+        c,
+        "MozLog to io::sink",
+        App::new().wrap(moz_log).route("/", web::get().to(handler))
+    );
+}
+
+fn mozlog_to_file(c: &mut Criterion) {
+    let path = std::env::temp_dir().join("tracing-actix-web-mozlog-bench.log");
+    let file = File::create(&path).expect("couldn't create the log file");
+    let moz_log = moz_log(Mutex::new(BufWriter::new(file)));
+    bench_requests!(
+        c,
+        "MozLog to BufWriter<File>",
+        App::new().wrap(moz_log).route("/", web::get().to(handler))
+    );
+    std::fs::remove_file(path).ok();
+}
+
+criterion_group!(benches, no_middleware, mozlog_to_sink, mozlog_to_file);
+criterion_main!(benches);
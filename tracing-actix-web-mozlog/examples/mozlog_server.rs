@@ -0,0 +1,98 @@
+//! Run server with:
+//!
+//! ```console
+//! $ cargo run --example=mozlog_server
+//! ```
+//!
+//! Test with:
+//!
+//! ```console
+//! curl http://localhost:8080/login
+//! curl http://localhost:8080/panic
+//! curl http://localhost:8080/metrics
+//! ```
+//!
+//! Each request is logged to stdout as a MozLog `request.summary` message.
+//! `/login` also logs a `user.login` message, and `/panic` logs the panic as an
+//! error before the worker recovers. `/metrics` reports request counts that are
+//! collected with a request end hook.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use actix_web::{get, web::Data, App, HttpServer, Responder};
+use tracing_actix_web_mozlog::{MozLog, MozLogSubscriberBuilder};
+use tracing_subscriber::EnvFilter;
+
+/// Request counts, by class of response status.
+#[derive(Default)]
+struct Metrics {
+    success: AtomicU64,
+    client_error: AtomicU64,
+    server_error: AtomicU64,
+}
+
+#[get("/login")]
+async fn login() -> impl Responder {
+    tracing::info!(r#type = "user.login", user = "example", "A user logged in");
+    "logged in\n"
+}
+
+#[get("/panic")]
+async fn fail() -> impl Responder {
+    panic!("something went wrong");
+    #[allow(unreachable_code)]
+    ""
+}
+
+#[get("/metrics")]
+async fn report_metrics(metrics: Data<Metrics>) -> impl Responder {
+    format!(
+        "success={} client_error={} server_error={}\n",
+        metrics.success.load(Ordering::Relaxed),
+        metrics.client_error.load(Ordering::Relaxed),
+        metrics.server_error.load(Ordering::Relaxed),
+    )
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    MozLogSubscriberBuilder::new("mozlog-example")
+        .with_env_filter(EnvFilter::new("info"))
+        .init()
+        .expect("could not set up logging");
+
+    // Log panics through tracing, so they are formatted as MozLog errors.
+    std::panic::set_hook(Box::new(|info| {
+        tracing::error!(r#type = "server.panic", panic = %info, "A handler panicked");
+    }));
+
+    let metrics = Data::new(Metrics::default());
+    let hook_metrics = metrics.clone();
+    let moz_log =
+        MozLog::default().with_request_end_hook(Arc::new(move |_request, response, _span| {
+            let counter = if response.status.is_server_error() {
+                &hook_metrics.server_error
+            } else if response.status.is_client_error() {
+                &hook_metrics.client_error
+            } else {
+                &hook_metrics.success
+            };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }));
+
+    println!("starting HTTP server at http://localhost:8080");
+    HttpServer::new(move || {
+        App::new()
+            .wrap(moz_log.clone())
+            .app_data(metrics.clone())
+            .service(login)
+            .service(fail)
+            .service(report_metrics)
+    })
+    .bind(("127.0.0.1", 8080))?
+    .run()
+    .await
+}
@@ -0,0 +1,134 @@
+//! Shortcuts for setting up a MozLog subscriber.
+
+use std::io::Stdout;
+
+use tracing::{subscriber::SetGlobalDefaultError, Level, Subscriber};
+use tracing_subscriber::{
+    fmt::MakeWriter,
+    layer::{Layer, SubscriberExt},
+    registry::LookupSpan,
+    EnvFilter, Registry,
+};
+
+use crate::{JsonStorageLayer, MozLogFormatLayer};
+
+/// Register a subscriber that logs all events to stdout in MozLog format as
+/// the global default.
+///
+/// This is a shortcut for `MozLogSubscriberBuilder::new(service_name).init()`.
+///
+/// # Errors
+///
+/// Returns an error if a global default subscriber has already been set.
+pub fn init(service_name: impl Into<String>) -> Result<(), SetGlobalDefaultError> {
+    MozLogSubscriberBuilder::new(service_name).init()
+}
+
+/// Builder for a subscriber that logs in MozLog format, combining a
+/// [`JsonStorageLayer`] and a [`MozLogFormatLayer`].
+///
+/// By default the subscriber logs all events to stdout.
+///
+/// ```no_run
+/// use tracing::Level;
+/// use tracing_actix_web_mozlog::MozLogSubscriberBuilder;
+/// use tracing_subscriber::EnvFilter;
+///
+/// MozLogSubscriberBuilder::new("service-name")
+///     .with_writer(std::io::stderr)
+///     .with_env_filter(EnvFilter::from_default_env())
+///     .with_type_required_for_level(Some(Level::INFO))
+///     .init()
+///     .expect("could not set up logging");
+/// ```
+pub struct MozLogSubscriberBuilder<W = fn() -> Stdout> {
+    service_name: String,
+    make_writer: W,
+    env_filter: Option<EnvFilter>,
+    type_required_for_level: Option<Level>,
+    json_storage: bool,
+}
+
+impl MozLogSubscriberBuilder {
+    /// Start building a subscriber that will log as `service_name`.
+    pub fn new(service_name: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+            make_writer: std::io::stdout,
+            env_filter: None,
+            type_required_for_level: None,
+            json_storage: true,
+        }
+    }
+}
+
+impl<W> MozLogSubscriberBuilder<W>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    /// Change the name that the service logs as, in the `Logger` field.
+    pub fn with_service_name(mut self, service_name: impl Into<String>) -> Self {
+        self.service_name = service_name.into();
+        self
+    }
+
+    /// Write log lines to `make_writer` instead of stdout.
+    pub fn with_writer<W2>(self, make_writer: W2) -> MozLogSubscriberBuilder<W2>
+    where
+        W2: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        MozLogSubscriberBuilder {
+            service_name: self.service_name,
+            make_writer,
+            env_filter: self.env_filter,
+            type_required_for_level: self.type_required_for_level,
+            json_storage: self.json_storage,
+        }
+    }
+
+    /// Only log events that are enabled by `filter`.
+    pub fn with_env_filter(mut self, filter: EnvFilter) -> Self {
+        self.env_filter = Some(filter);
+        self
+    }
+
+    /// If `required`, drop all events that don't have a `type` field. This is
+    /// the same as `with_type_required_for_level(Some(Level::ERROR))`.
+    pub fn with_type_required(self, required: bool) -> Self {
+        self.with_type_required_for_level(required.then_some(Level::ERROR))
+    }
+
+    /// See [`MozLogFormatLayer::with_type_required_for_level`].
+    pub fn with_type_required_for_level(mut self, level: Option<Level>) -> Self {
+        self.type_required_for_level = level;
+        self
+    }
+
+    /// Choose whether to include a [`JsonStorageLayer`] in the subscriber,
+    /// which is enabled by default. Without it, fields from the spans
+    /// enclosing an event are not logged.
+    pub fn with_json_storage(mut self, enabled: bool) -> Self {
+        self.json_storage = enabled;
+        self
+    }
+
+    /// Build the configured subscriber.
+    pub fn build(self) -> impl Subscriber + for<'a> LookupSpan<'a> + Send + Sync {
+        let format_layer = MozLogFormatLayer::new(self.service_name, self.make_writer)
+            .with_type_required_for_level(self.type_required_for_level)
+            .with_filter(self.env_filter);
+
+        Registry::default()
+            .with(self.json_storage.then_some(JsonStorageLayer))
+            .with(format_layer)
+    }
+
+    /// Build the configured subscriber and register it as the global default.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a global default subscriber has already been set.
+    pub fn init(self) -> Result<(), SetGlobalDefaultError> {
+        tracing::subscriber::set_global_default(self.build())
+    }
+}
@@ -26,6 +26,17 @@
 //! [`tracing::subscriber::set_global_default`], or any other registration
 //! method. It will manage formatting any events logged in MozLog JSON format.
 //!
+//! For the common case of logging to stdout, [`init`] does all of the above:
+//!
+//! ```no_run
+//! tracing_actix_web_mozlog::init("service-name").expect("could not set up logging");
+//! ```
+//!
+//! To control which events are logged at runtime, use
+//! [`MozLogFormatLayer::with_default_env_filter`] to apply the directives in
+//! the `RUST_LOG` environment variable, or
+//! [`MozLogFormatLayer::with_env_filter`] to use a custom filter.
+//!
 //! Fields defined on the enclosing spans of an event will be included when
 //! logging an event. The event overrides the spans, and inner spans override
 //! outer spans.
@@ -47,8 +58,8 @@
 //! });
 //! ```
 //!
-//! This middleware will emit `request.summary` events for each request as it is
-//! completed, including timing information.
+//! This middleware will emit `request.summary` events for each request once its
+//! response body has been sent, including timing information.
 //!
 //! ## Message Types
 //!
@@ -83,11 +94,24 @@
 #![warn(rustdoc::missing_crate_level_docs)]
 #![warn(missing_docs)]
 
+mod builder;
+#[cfg(feature = "log-compat")]
+mod log_compat;
 mod middleware;
+#[cfg(feature = "opentelemetry")]
+mod otel;
 mod subscriber;
+mod syslog;
 
-pub use crate::middleware::MozLog;
-pub use crate::subscriber::{MozLogFormatLayer, MozLogMessage};
+pub use crate::builder::{init, MozLogSubscriberBuilder};
+#[cfg(feature = "log-compat")]
+pub use crate::log_compat::init_log_compat;
+pub use crate::middleware::{MozLog, RequestEndHook};
+#[cfg(feature = "opentelemetry")]
+pub use crate::otel::MozLogOtelBridgeLayer;
+#[cfg(feature = "file-log")]
+pub use crate::subscriber::Rotation;
+pub use crate::subscriber::{FilteredMozLogFormatLayer, MozLogFormatLayer, MozLogMessage};
 
 /// A layer to collect information about Tracing spans and provide it to other layers.
 ///
@@ -0,0 +1,69 @@
+//! Compatibility with the [`log`] crate, for code that logs with `log::info!`
+//! and similar macros instead of Tracing.
+
+use log::kv::Source;
+use serde_json::Value;
+
+use crate::MozLogMessage;
+
+/// Forward all records logged with the [`log`] crate to Tracing, so that they
+/// are logged in MozLog format by a [`MozLogFormatLayer`](crate::MozLogFormatLayer)
+/// like Tracing events.
+///
+/// The record's message is logged as the `message` field, and its target
+/// and source location as the `log.target`, `log.module_path`, `log.file`
+/// and `log.line` fields.
+///
+/// # Errors
+///
+/// Returns an error if a global logger has already been set.
+pub fn init_log_compat() -> Result<(), log::SetLoggerError> {
+    tracing_log::LogTracer::init()
+}
+
+impl MozLogMessage {
+    /// Call `f` with this message as a [`log::Record`], such as to pass it to
+    /// a [`log::Log`] implementation.
+    ///
+    /// The message's type is the target of the record, and its severity is
+    /// the level. The `message` field is the record's message, and the other
+    /// fields are its key-values, with non-string values as JSON.
+    pub fn with_log_record<R>(&self, f: impl FnOnce(&log::Record<'_>) -> R) -> R {
+        let message = match self.fields.get("message") {
+            Some(Value::String(message)) => message.as_str(),
+            _ => "",
+        };
+        let mut key_values: Vec<(&str, String)> = self
+            .fields
+            .iter()
+            .filter(|(name, _)| name.as_str() != "message")
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (name.as_str(), value)
+            })
+            .collect();
+        key_values.sort();
+
+        f(&log::Record::builder()
+            .level(log_level(self.severity))
+            .target(&self.message_type)
+            .args(format_args!("{}", message))
+            .key_values(&key_values as &dyn Source)
+            .build())
+    }
+}
+
+/// The [`log::Level`] for a Syslog severity, the inverse of the mapping used
+/// for Tracing levels. Severities more severe than an error are errors.
+fn log_level(severity: u32) -> log::Level {
+    match severity {
+        0..=3 => log::Level::Error,
+        4 => log::Level::Warn,
+        5 => log::Level::Info,
+        6 => log::Level::Debug,
+        _ => log::Level::Trace,
+    }
+}
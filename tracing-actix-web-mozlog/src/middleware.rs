@@ -3,17 +3,22 @@
 use std::{
     future::Future,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
     time::Instant,
 };
 
 use actix_web::{
-    body::MessageBody,
-    dev::{Service, ServiceRequest, ServiceResponse, Transform},
-    HttpMessage,
+    body::{self, EitherBody, MessageBody},
+    dev::{ResponseHead, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::HeaderValue,
+    middleware::Condition,
+    HttpMessage, HttpRequest,
 };
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::TryFutureExt;
 use tracing::{Dispatch, Span};
-use tracing_actix_web::{RequestId, RootSpanBuilder, TracingLogger};
+use tracing_actix_web::{RequestId, RootSpan, RootSpanBuilder, TracingLogger};
 use tracing_futures::WithSubscriber;
 
 /// Middleware factory that implements the request/response cycle logging
@@ -36,12 +41,17 @@ use tracing_futures::WithSubscriber;
 /// });
 /// ```
 ///
-/// This middleware will emit `request.summary` events for each request as it is
-/// completed, including timing information.
+/// This middleware will emit `request.summary` events for each request once its
+/// response body has been sent, including timing information.
+///
+/// The request span also declares `location.country`, `location.region` and
+/// `location.city` fields, which `actix-web-location` records when its tracing
+/// integration is enabled.
 #[derive(Clone)]
 pub struct MozLog {
     dispatch: Dispatch,
     tracing_logger: TracingLogger<MozLogRootSpanBuilder>,
+    options: Arc<MozLogOptions>,
 }
 
 impl Default for MozLog {
@@ -51,10 +61,185 @@ impl Default for MozLog {
         Self {
             dispatch: dispatch.unwrap(),
             tracing_logger: TracingLogger::new(),
+            options: Arc::new(MozLogOptions::default()),
         }
     }
 }
 
+impl MozLog {
+    /// Create a default `MozLog` middleware that only logs requests if
+    /// `enabled` is true, so that logging can be configured without
+    /// branching when building the app:
+    ///
+    /// ```
+    /// use tracing_actix_web_mozlog::MozLog;
+    /// use actix_web::App;
+    ///
+    /// let enable_mozlog = false;
+    /// let app = App::new().wrap(MozLog::conditional(enable_mozlog));
+    /// ```
+    ///
+    /// When disabled, requests pass through unchanged. To conditionally
+    /// enable a customized middleware, use [`Condition`] directly.
+    pub fn conditional(enabled: bool) -> Condition<Self> {
+        Condition::new(enabled, Self::default())
+    }
+
+    /// Record the value of the request header `header_name` as the `trace_id`
+    /// field of the request span, such as `traceparent` or `X-B3-TraceId`. This
+    /// allows joining request logs across services that propagate a
+    /// distributed trace ID.
+    pub fn with_correlation_id_header(mut self, header_name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.options).correlation_id_header = Some(header_name.into());
+        self
+    }
+
+    /// When enabled, `request.summary` events for failed requests include an
+    /// `error` field with the structure `{"code": 500, "message": "..."}`, in
+    /// addition to the standard `errno` and `msg` fields.
+    ///
+    /// The error's type is not included. Errors reach the middleware as
+    /// `dyn ResponseError`, whose concrete type can't be named at runtime, and
+    /// neither `type_name_of_val` nor the `Debug` output gives a reliable name.
+    ///
+    /// Note that nested objects are not part of the MozLog schema, so this is
+    /// disabled by default.
+    pub fn with_structured_error_field(mut self, enabled: bool) -> Self {
+        Arc::make_mut(&mut self.options).structured_error_field = enabled;
+        self
+    }
+
+    /// Record up to `max_bytes` of the response body as the `response_body`
+    /// field of the request span, for responses with one of the given status
+    /// codes. This is intended for debugging, such as capturing the details of
+    /// `400` responses.
+    ///
+    /// Streaming bodies aren't buffered: at most `max_bytes` are copied as
+    /// they are sent, and recorded once that many bytes have been sent or the
+    /// body ends. Either way, the field is included in the `request.summary`
+    /// event.
+    pub fn with_response_body_logging_for_status(
+        mut self,
+        status_codes: Vec<u16>,
+        max_bytes: usize,
+    ) -> Self {
+        Arc::make_mut(&mut self.options).response_body_capture = Some(ResponseBodyCapture {
+            status_codes,
+            max_bytes,
+        });
+        self
+    }
+
+    /// When enabled, `request.summary` events also include a `t_us` field with
+    /// the request duration in microseconds, alongside the millisecond `t` and
+    /// nanosecond `t_ns` fields. This is useful for very fast requests, such as
+    /// health checks, which often take less than a millisecond.
+    pub fn with_microsecond_precision(mut self, enabled: bool) -> Self {
+        Arc::make_mut(&mut self.options).microsecond_precision = enabled;
+        self
+    }
+
+    /// When enabled, the number of response body bytes actually written is
+    /// recorded as the `bytes_sent` field of the request span. Unlike the
+    /// `Content-Length` header, this reflects any compression applied by
+    /// middleware registered before `MozLog`, and bodies that are cut short.
+    pub fn with_transfer_size_tracking(mut self, enabled: bool) -> Self {
+        Arc::make_mut(&mut self.options).transfer_size_tracking = enabled;
+        self
+    }
+
+    /// Record the values of the response headers named in `headers` on
+    /// `request.summary` events, such as `Cache-Control` or `ETag`.
+    ///
+    /// Each field is named after its header, lowercased and with characters
+    /// other than letters and digits replaced by `_`, so `Cache-Control` is
+    /// logged as `cache_control`. Headers that aren't in a response are
+    /// omitted.
+    pub fn with_response_header_capture(mut self, headers: Vec<String>) -> Self {
+        Arc::make_mut(&mut self.options)
+            .captured_response_headers
+            .extend(headers);
+        self
+    }
+
+    /// Call `hook` when the response to each request is ready, before its body
+    /// is sent and the `request.summary` event is logged. The hook receives the
+    /// request, the head of the response, and the request span. Only fields
+    /// that the middleware declares on the span can be recorded, such as
+    /// `uid`.
+    ///
+    /// Application data is available through the request, such as with
+    /// `request.app_data::<web::Data<T>>()`. The hook isn't called for
+    /// requests that fail before a response is produced.
+    pub fn with_request_end_hook(mut self, hook: RequestEndHook) -> Self {
+        Arc::make_mut(&mut self.options).request_end_hook = Some(hook);
+        self
+    }
+
+    /// Record the claim `claim` of the JWT in `Authorization: Bearer` request
+    /// headers as the `uid` field of the request span, such as `"sub"`.
+    ///
+    /// The token is only decoded, and its signature is **not** verified, so
+    /// the logged value must not be trusted for anything but diagnostics.
+    pub fn with_uid_from_jwt(mut self, claim: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.options).uid_jwt_claim = Some(claim.into());
+        self
+    }
+
+    /// Don't assign request IDs, for services behind a reverse proxy that
+    /// already assigns and logs its own. The `rid` field is omitted from
+    /// request logs.
+    ///
+    /// Handlers must not use the [`RequestId`] extractor when this is set, as
+    /// it will fail to find a request ID.
+    pub fn with_no_request_id(mut self) -> Self {
+        Arc::make_mut(&mut self.options).no_request_id = true;
+        self
+    }
+
+    /// Add `fields` to every event logged within a request, including the
+    /// `request.summary` event. This is useful for deployment metadata such
+    /// as the datacenter or environment.
+    ///
+    /// Fields set by the request itself, such as `code` or `path`, take
+    /// precedence over default fields with the same name.
+    pub fn with_default_fields(
+        mut self,
+        fields: Vec<(&'static str, impl Into<serde_json::Value>)>,
+    ) -> Self {
+        Arc::make_mut(&mut self.options)
+            .default_fields
+            .extend(fields.into_iter().map(|(key, value)| (key, value.into())));
+        self
+    }
+}
+
+/// Per-middleware configuration, made available to [`MozLogRootSpanBuilder`]
+/// through the request extensions.
+#[derive(Clone, Default)]
+struct MozLogOptions {
+    correlation_id_header: Option<String>,
+    structured_error_field: bool,
+    response_body_capture: Option<ResponseBodyCapture>,
+    default_fields: Vec<(&'static str, serde_json::Value)>,
+    microsecond_precision: bool,
+    captured_response_headers: Vec<String>,
+    transfer_size_tracking: bool,
+    request_end_hook: Option<RequestEndHook>,
+    uid_jwt_claim: Option<String>,
+    no_request_id: bool,
+}
+
+/// A function called with each completed request, from
+/// [`MozLog::with_request_end_hook`].
+pub type RequestEndHook = Arc<dyn Fn(&HttpRequest, &ResponseHead, &Span) + Send + Sync>;
+
+#[derive(Clone)]
+struct ResponseBodyCapture {
+    status_codes: Vec<u16>,
+    max_bytes: usize,
+}
+
 impl<S, B> Transform<S, ServiceRequest> for MozLog
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
@@ -63,24 +248,29 @@ where
     S: 'static,
 {
     type Response =
-        <TracingLogger<MozLogRootSpanBuilder> as Transform<S, ServiceRequest>>::Response;
+        <MozLogMiddleware<TracingLoggerMiddleware<S>> as Service<ServiceRequest>>::Response;
     type Error = actix_web::Error;
-    type Transform = MozLogMiddleware<
-        <TracingLogger<MozLogRootSpanBuilder> as Transform<S, ServiceRequest>>::Transform,
-    >;
+    type Transform = MozLogMiddleware<TracingLoggerMiddleware<S>>;
     type InitError = ();
     type Future = MozLogTransform<S, B>;
 
     fn new_transform(&self, service: S) -> Self::Future {
+        let service = ResponseBodyMiddleware {
+            service,
+            options: self.options.clone(),
+        };
         MozLogTransform {
             inner: Box::pin(self.tracing_logger.new_transform(service)),
             dispatch: self.dispatch.clone(),
+            options: self.options.clone(),
         }
     }
 }
 
-type TracingLoggerMiddleware<S> =
-    <TracingLogger<MozLogRootSpanBuilder> as Transform<S, ServiceRequest>>::Transform;
+type TracingLoggerMiddleware<S> = <TracingLogger<MozLogRootSpanBuilder> as Transform<
+    ResponseBodyMiddleware<S>,
+    ServiceRequest,
+>>::Transform;
 
 type ServiceFuture<T, E> = Pin<Box<dyn Future<Output = Result<T, E>>>>;
 
@@ -91,6 +281,7 @@ where
     B: 'static + MessageBody,
 {
     dispatch: Dispatch,
+    options: Arc<MozLogOptions>,
     inner: Pin<Box<dyn Future<Output = Result<TracingLoggerMiddleware<S>, ()>>>>,
 }
 
@@ -107,6 +298,7 @@ where
             Poll::Ready(Ok(inner)) => Poll::Ready(Ok(MozLogMiddleware {
                 service: inner,
                 dispatch: self.dispatch.clone(),
+                options: self.options.clone(),
             })),
             Poll::Ready(Err(_)) => Poll::Ready(Err(())),
             Poll::Pending => Poll::Pending,
@@ -117,15 +309,16 @@ where
 pub struct MozLogMiddleware<S> {
     service: S,
     dispatch: Dispatch,
+    options: Arc<MozLogOptions>,
 }
 
 impl<S, B> Service<ServiceRequest> for MozLogMiddleware<S>
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
     S::Future: 'static,
-    B: 'static,
+    B: 'static + MessageBody,
 {
-    type Response = ServiceResponse<B>;
+    type Response = ServiceResponse<SubscriberBody<B>>;
     type Error = actix_web::Error;
     type Future = ServiceFuture<Self::Response, Self::Error>;
 
@@ -134,16 +327,314 @@ where
     }
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
+        req.extensions_mut().insert(self.options.clone());
+        let dispatch = self.dispatch.clone();
         Box::pin(
             self.service
                 .call(req)
+                .map_ok(|response| response.map_body(|_, inner| SubscriberBody { inner, dispatch }))
                 .with_subscriber(self.dispatch.clone()),
         )
     }
 }
 
+pin_project_lite::pin_project! {
+    /// A response body that is polled with the middleware's subscriber as the
+    /// default. Actix sends the body after the request's future completes, so
+    /// without this, events logged while streaming a body would be lost.
+    pub struct SubscriberBody<B> {
+        #[pin]
+        inner: B,
+        dispatch: Dispatch,
+    }
+}
+
+impl<B: MessageBody> MessageBody for SubscriberBody<B> {
+    type Error = B::Error;
+
+    fn size(&self) -> body::BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<actix_web::web::Bytes, Self::Error>>> {
+        let this = self.project();
+        let inner = this.inner;
+        tracing::dispatcher::with_default(this.dispatch, || inner.poll_next(cx))
+    }
+}
+
+/// Middleware that runs inside of [`TracingLogger`], so that the response body
+/// and errors can be recorded on the root span before the request summary is
+/// logged.
+pub struct ResponseBodyMiddleware<S> {
+    service: S,
+    options: Arc<MozLogOptions>,
+}
+
+impl<S, B> Service<ServiceRequest> for ResponseBodyMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    S::Future: 'static,
+    B: 'static + MessageBody,
+{
+    type Response = ServiceResponse<SummaryBody>;
+    type Error = actix_web::Error;
+    type Future = ServiceFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let root_span = req.extensions().get::<RootSpan>().cloned();
+        let fut = self.service.call(req);
+        let options = self.options.clone();
+
+        Box::pin(async move {
+            let response = match fut.await {
+                Ok(response) => response,
+                Err(error) => {
+                    // The request isn't available to `on_request_end` when
+                    // there is no response, so record the error here.
+                    if let Some(root_span) = root_span.filter(|_| options.structured_error_field) {
+                        record_structured_error(&root_span, &error);
+                    }
+                    return Err(error);
+                }
+            };
+            let response = capture_response_body(response, &options);
+            let response = if options.transfer_size_tracking {
+                let root_span = response.request().extensions().get::<RootSpan>().cloned();
+                response
+                    .map_body(|_, body| TransferSizeBody {
+                        inner: body::BoxBody::new(body),
+                        bytes_sent: 0,
+                        root_span,
+                    })
+                    .map_into_boxed_body()
+            } else {
+                response.map_into_boxed_body()
+            };
+
+            let extensions = response.request().extensions();
+            let root_span = extensions.get::<RootSpan>().cloned();
+            let start = extensions
+                .get::<RequestStart>()
+                .map_or_else(Instant::now, |start| start.0);
+            drop(extensions);
+            let mut dispatch = None;
+            tracing::dispatcher::get_default(|d| dispatch = Some(d.clone()));
+            Ok(response.map_body(|_, inner| SummaryBody {
+                inner,
+                root_span,
+                start,
+                microsecond_precision: options.microsecond_precision,
+                dispatch: dispatch.unwrap(),
+            }))
+        })
+    }
+}
+
+/// A response body that logs the `request.summary` event once it is finished
+/// or dropped, so that the request timing includes sending the body.
+pub struct SummaryBody {
+    inner: body::BoxBody,
+    root_span: Option<RootSpan>,
+    start: Instant,
+    microsecond_precision: bool,
+    dispatch: Dispatch,
+}
+
+impl SummaryBody {
+    fn finish(&mut self) {
+        if let Some(root_span) = self.root_span.take() {
+            // Drop the inner body first, so that any fields it records on the
+            // root span when it ends are included in the summary.
+            drop(std::mem::replace(&mut self.inner, body::BoxBody::new(())));
+
+            let elapsed = self.start.elapsed();
+            root_span.record("t", elapsed.as_millis() as u32);
+            root_span.record("t_ns", elapsed.as_nanos() as u64);
+            if self.microsecond_precision {
+                root_span.record("t_us", elapsed.as_micros() as u64);
+            }
+            tracing::dispatcher::with_default(&self.dispatch, || {
+                root_span.in_scope(|| tracing::info!(r#type = "request.summary"));
+            });
+        }
+    }
+}
+
+impl MessageBody for SummaryBody {
+    type Error = <body::BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> body::BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<actix_web::web::Bytes, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        if let Poll::Ready(None) = poll {
+            self.finish();
+        }
+        poll
+    }
+}
+
+impl Drop for SummaryBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// Record the response body on the root span, if the middleware options
+/// request it for the response's status code.
+fn capture_response_body<B: MessageBody + 'static>(
+    response: ServiceResponse<B>,
+    options: &MozLogOptions,
+) -> ServiceResponse<EitherBody<B>> {
+    let max_bytes = match &options.response_body_capture {
+        Some(capture) if capture.status_codes.contains(&response.status().as_u16()) => {
+            capture.max_bytes
+        }
+        _ => return response.map_into_left_body(),
+    };
+    let root_span = response.request().extensions().get::<RootSpan>().cloned();
+    let root_span = match root_span {
+        Some(root_span) => root_span,
+        None => return response.map_into_left_body(),
+    };
+
+    let (request, response) = response.into_parts();
+    let (response, body) = response.into_parts();
+    let body = match body.try_into_bytes() {
+        Ok(bytes) => {
+            let captured = &bytes[..bytes.len().min(max_bytes)];
+            root_span.record("response_body", String::from_utf8_lossy(captured).as_ref());
+            body::BoxBody::new(bytes)
+        }
+        Err(body) => body::BoxBody::new(CaptureBody {
+            inner: body::BoxBody::new(body),
+            captured: Vec::new(),
+            max_bytes,
+            root_span: Some(root_span),
+        }),
+    };
+
+    let response = response.set_body(body);
+    ServiceResponse::new(request, response).map_into_right_body()
+}
+
+/// A streaming response body that copies up to `max_bytes` of itself as it is
+/// sent, and records them on the root span once the limit is reached or the
+/// body is finished or dropped.
+struct CaptureBody {
+    inner: body::BoxBody,
+    captured: Vec<u8>,
+    max_bytes: usize,
+    root_span: Option<RootSpan>,
+}
+
+impl CaptureBody {
+    fn finish(&mut self) {
+        if let Some(root_span) = self.root_span.take() {
+            root_span.record(
+                "response_body",
+                String::from_utf8_lossy(&self.captured).as_ref(),
+            );
+            self.captured = Vec::new();
+        }
+    }
+}
+
+impl MessageBody for CaptureBody {
+    type Error = <body::BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> body::BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<actix_web::web::Bytes, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) if self.root_span.is_some() => {
+                let remaining = self.max_bytes - self.captured.len();
+                self.captured
+                    .extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+                if self.captured.len() >= self.max_bytes {
+                    self.finish();
+                }
+            }
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for CaptureBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+/// A response body that counts the bytes written from it, and records the
+/// count on the root span once the body is finished or dropped.
+struct TransferSizeBody {
+    inner: body::BoxBody,
+    bytes_sent: u64,
+    root_span: Option<RootSpan>,
+}
+
+impl TransferSizeBody {
+    fn finish(&mut self) {
+        if let Some(root_span) = self.root_span.take() {
+            root_span.record("bytes_sent", self.bytes_sent);
+        }
+    }
+}
+
+impl MessageBody for TransferSizeBody {
+    type Error = <body::BoxBody as MessageBody>::Error;
+
+    fn size(&self) -> body::BodySize {
+        self.inner.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<actix_web::web::Bytes, Self::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => self.bytes_sent += chunk.len() as u64,
+            Poll::Ready(None) => self.finish(),
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for TransferSizeBody {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
 /// A root span builder for tracing_actix_web to customize the extra fields we
-/// log with requests, and to log an event when requests end.
+/// log with requests.
+///
+/// The `request.summary` event is logged by [`SummaryBody`] once the response
+/// body is sent, or here if there is no response.
 pub struct MozLogRootSpanBuilder;
 
 struct RequestStart(Instant);
@@ -153,15 +644,26 @@ impl RootSpanBuilder for MozLogRootSpanBuilder {
         let http_method = request.method().as_str();
 
         let mut request_extensions = request.extensions_mut();
-        let request_id = request_extensions.get::<RequestId>().cloned().unwrap();
         request_extensions.insert(RequestStart(Instant::now()));
+        let options = request_extensions
+            .get::<Arc<MozLogOptions>>()
+            .cloned()
+            .unwrap_or_default();
+        // `TracingLogger` always generates a request ID, so remove it when
+        // request IDs are disabled.
+        let request_id = if options.no_request_id {
+            request_extensions.remove::<RequestId>();
+            None
+        } else {
+            request_extensions.get::<RequestId>().cloned()
+        };
 
         let span = tracing::info_span!(
             "request",
             method = %http_method,
             path = %request.uri().path(),
             code = tracing::field::Empty,
-            rid = %request_id,
+            rid = tracing::field::Empty,
             errno = tracing::field::Empty,
             agent = tracing::field::Empty,
             msg = tracing::field::Empty,
@@ -169,10 +671,65 @@ impl RootSpanBuilder for MozLogRootSpanBuilder {
             uid = tracing::field::Empty,
             t = tracing::field::Empty,
             t_ns = tracing::field::Empty,
+            t_us = tracing::field::Empty,
+            trace_id = tracing::field::Empty,
+            forwarded_for = tracing::field::Empty,
+            response_body = tracing::field::Empty,
+            "mozlog.spread.default_fields" = tracing::field::Empty,
+            "mozlog.spread.response_headers" = tracing::field::Empty,
+            bytes_sent = tracing::field::Empty,
+            "location.country" = tracing::field::Empty,
+            "location.region" = tracing::field::Empty,
+            "location.city" = tracing::field::Empty,
+            "mozlog.json.error" = tracing::field::Empty,
         );
 
+        if let Some(request_id) = request_id {
+            span.record("rid", tracing::field::display(request_id));
+        }
+
         if let Some(user_agent) = request.headers().get("User-Agent") {
-            span.record("agent", &user_agent.to_str().unwrap_or("<bad_utf8>"));
+            span.record("agent", user_agent.to_str().unwrap_or("<bad_utf8>"));
+        }
+
+        if let Some(forwarded_for) = request.headers().get("X-Forwarded-For") {
+            span.record(
+                "forwarded_for",
+                forwarded_for.to_str().unwrap_or("<bad_utf8>"),
+            );
+        }
+
+        if let Some(trace_id) = options
+            .correlation_id_header
+            .as_ref()
+            .and_then(|header_name| request.headers().get(header_name.as_str()))
+        {
+            span.record("trace_id", trace_id.to_str().unwrap_or("<bad_utf8>"));
+        }
+
+        if let Some(uid) = options
+            .uid_jwt_claim
+            .as_ref()
+            .and_then(|claim| jwt_claim(request.headers().get("Authorization")?, claim))
+        {
+            span.record("uid", uid.as_str());
+        }
+
+        if !options.default_fields.is_empty() {
+            // Span fields can't be named at runtime, so the default fields are
+            // recorded as a single JSON object that `MozLogFormatLayer` spreads
+            // into the message.
+            let default_fields: serde_json::Map<String, serde_json::Value> = options
+                .default_fields
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect();
+            span.record(
+                "mozlog.spread.default_fields",
+                serde_json::Value::Object(default_fields)
+                    .to_string()
+                    .as_str(),
+            );
         }
 
         span
@@ -181,31 +738,116 @@ impl RootSpanBuilder for MozLogRootSpanBuilder {
     fn on_request_end<B>(span: Span, outcome: &Result<ServiceResponse<B>, actix_web::Error>) {
         match &outcome {
             Ok(response) => {
-                if let Some(req_start) = response.request().extensions().get::<RequestStart>() {
-                    let elapsed = req_start.0.elapsed();
-                    span.record("t", &(elapsed.as_millis() as u32));
-                    span.record("t_ns", &(elapsed.as_nanos() as u64));
+                let options = response
+                    .request()
+                    .extensions()
+                    .get::<Arc<MozLogOptions>>()
+                    .cloned()
+                    .unwrap_or_default();
+
+                record_response_headers(&span, response, &options.captured_response_headers);
+
+                if let Some(hook) = &options.request_end_hook {
+                    hook(response.request(), response.response().head(), &span);
                 }
 
                 if let Some(error) = response.response().error() {
-                    handle_error(span, error);
+                    handle_error(span, error, options.structured_error_field);
                 } else {
-                    span.record("code", &response.response().status().as_u16());
+                    span.record("code", response.response().status().as_u16());
                     response.status();
                 }
             }
-            Err(error) => handle_error(span, error),
-        };
+            // The request, and so the middleware options, aren't available
+            // here, so `ResponseBodyMiddleware` records the structured error.
+            Err(error) => {
+                handle_error(span, error, false);
+                tracing::info!(r#type = "request.summary");
+            }
+        }
+    }
+}
 
-        tracing::info!(r#type = "request.summary")
+/// Read the claim `claim` from the payload of the JWT in the `Authorization`
+/// header value `authorization`, without verifying the token. String claims
+/// are returned as-is, and other values as JSON.
+fn jwt_claim(authorization: &HeaderValue, claim: &str) -> Option<String> {
+    let token = authorization.to_str().ok()?.strip_prefix("Bearer ")?;
+    let payload = token.trim().split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let mut claims: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_slice(&payload).ok()?;
+    match claims.remove(claim)? {
+        serde_json::Value::String(value) => Some(value),
+        serde_json::Value::Null => None,
+        value => Some(value.to_string()),
+    }
+}
+
+/// Record the values of `header_names` in `response` on the root request span.
+///
+/// Span fields must be declared when the span is created, so the headers are
+/// recorded as a single JSON object that [`crate::MozLogFormatLayer`] spreads
+/// into the message.
+fn record_response_headers<B>(span: &Span, response: &ServiceResponse<B>, header_names: &[String]) {
+    let headers: serde_json::Map<String, serde_json::Value> = header_names
+        .iter()
+        .filter_map(|name| {
+            let value = response.headers().get(name.as_str())?;
+            Some((
+                header_field_name(name),
+                value.to_str().unwrap_or("<bad_utf8>").into(),
+            ))
+        })
+        .collect();
+    if !headers.is_empty() {
+        span.record(
+            "mozlog.spread.response_headers",
+            serde_json::Value::Object(headers).to_string().as_str(),
+        );
     }
 }
 
+/// Convert a header name to a field name, such as `cache_control` for
+/// `Cache-Control`.
+fn header_field_name(header_name: &str) -> String {
+    header_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
 /// Annotate the root request span with information about a request error.
-fn handle_error(span: Span, error: &actix_web::Error) {
+///
+/// If `structured` is set, the error is also recorded with
+/// [`record_structured_error`].
+fn handle_error(span: Span, error: &actix_web::Error, structured: bool) {
     let response_error = error.as_response_error();
     let status = response_error.status_code();
-    span.record("errno", &1);
-    span.record("msg", &tracing::field::display(response_error));
-    span.record("code", &status.as_u16());
+    span.record("errno", 1);
+    span.record("msg", tracing::field::display(response_error));
+    span.record("code", status.as_u16());
+
+    if structured {
+        record_structured_error(&span, error);
+    }
+}
+
+/// Record `error` as JSON in the `mozlog.json.error` field of the root request
+/// span, which [`crate::MozLogFormatLayer`] logs as an `error` object.
+fn record_structured_error(span: &Span, error: &actix_web::Error) {
+    let response_error = error.as_response_error();
+    let mut fields = serde_json::Map::new();
+    fields.insert("code".into(), response_error.status_code().as_u16().into());
+    fields.insert("message".into(), response_error.to_string().into());
+    span.record(
+        "mozlog.json.error",
+        serde_json::Value::Object(fields).to_string().as_str(),
+    );
 }
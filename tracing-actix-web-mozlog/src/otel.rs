@@ -0,0 +1,128 @@
+//! A bridge from MozLog events to OpenTelemetry spans.
+
+use std::time::{Duration, SystemTime};
+
+use opentelemetry::{
+    trace::{Span, SpanKind, Status, Tracer},
+    KeyValue,
+};
+use serde_json::Value;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::{fmt::MakeWriter, layer::Context, registry::LookupSpan, Layer};
+
+use crate::{MozLogFormatLayer, MozLogMessage};
+
+/// A layer that logs events like [`MozLogFormatLayer`], and also reports each
+/// logged event to OpenTelemetry as a span. This allows running MozLog and
+/// OpenTelemetry side by side while migrating between them.
+///
+/// Each span is named after the event's MozLog `type`, has the event's fields
+/// as attributes, and starts and ends at the event's timestamp. Since events
+/// don't have a duration or a relationship to other services, all spans are
+/// [`SpanKind::Internal`]. Events with a severity of error or worse have an
+/// error status, and the MozLog severity is recorded in the
+/// `mozlog.severity` attribute.
+///
+/// # Example
+///
+/// ```
+/// use opentelemetry::trace::noop::NoopTracer;
+/// use tracing_actix_web_mozlog::{JsonStorageLayer, MozLogFormatLayer, MozLogOtelBridgeLayer};
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(JsonStorageLayer)
+///     .with(MozLogOtelBridgeLayer::new(
+///         MozLogFormatLayer::new("service-name", std::io::stdout),
+///         NoopTracer::new(),
+///     ));
+/// ```
+pub struct MozLogOtelBridgeLayer<W, T>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    format_layer: MozLogFormatLayer<W>,
+    tracer: T,
+}
+
+impl<W, T> MozLogOtelBridgeLayer<W, T>
+where
+    W: for<'a> MakeWriter<'a> + 'static,
+    T: Tracer,
+{
+    /// Log events with `format_layer`, and also report them as spans to `tracer`.
+    pub fn new(format_layer: MozLogFormatLayer<W>, tracer: T) -> Self {
+        Self {
+            format_layer,
+            tracer,
+        }
+    }
+
+    fn report(&self, message: &MozLogMessage) {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_nanos(message.timestamp);
+
+        let mut attributes = vec![
+            KeyValue::new("mozlog.logger", message.logger.clone()),
+            KeyValue::new("mozlog.severity", i64::from(message.severity)),
+        ];
+        attributes.extend(
+            message
+                .fields
+                .iter()
+                .map(|(key, value)| KeyValue::new(key.clone(), attribute_value(value))),
+        );
+
+        let status = if message.severity <= 3 {
+            let description = message
+                .fields
+                .get("msg")
+                .or_else(|| message.fields.get("message"))
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            Status::error(description)
+        } else {
+            Status::Unset
+        };
+
+        let mut span = self
+            .tracer
+            .span_builder(message.message_type.clone())
+            .with_kind(SpanKind::Internal)
+            .with_start_time(timestamp)
+            .with_attributes(attributes)
+            .with_status(status)
+            .start(&self.tracer);
+        span.end_with_timestamp(timestamp);
+    }
+}
+
+/// Convert a MozLog field to an OpenTelemetry attribute value. Values that
+/// OpenTelemetry can't represent directly are stored as JSON strings.
+fn attribute_value(value: &Value) -> opentelemetry::Value {
+    match value {
+        Value::Bool(b) => (*b).into(),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => i.into(),
+            (None, Some(f)) => f.into(),
+            (None, None) => n.to_string().into(),
+        },
+        Value::String(s) => s.clone().into(),
+        other => other.to_string().into(),
+    }
+}
+
+impl<S, W, T> Layer<S> for MozLogOtelBridgeLayer<W, T>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+    T: Tracer + Send + Sync + 'static,
+    T::Span: Send + Sync + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if let Some(message) = self.format_layer.format_event(event, &ctx) {
+            self.format_layer.write_message(&message, event.metadata());
+            self.report(&message);
+        }
+    }
+}
@@ -1,13 +1,28 @@
 use gethostname::gethostname;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::{collections::HashMap, io::Write, time::SystemTime};
-use tracing::{Event, Level, Subscriber};
+use std::{collections::HashMap, fmt, io::Write, sync::Arc, time::SystemTime};
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Metadata, Subscriber,
+};
 use tracing_bunyan_formatter::JsonStorage;
-use tracing_subscriber::{fmt::MakeWriter, layer::Context};
+use tracing_subscriber::{
+    filter::{EnvFilter, Filtered},
+    fmt::MakeWriter,
+    layer::{Context, Layer},
+};
 
 const MOZLOG_VERSION: &str = "2.0";
 
+/// The prefix of span fields that are recorded as JSON and logged as a nested
+/// value. See [`MozLogFormatLayer`].
+const JSON_FIELD_PREFIX: &str = "mozlog.json.";
+
+/// The prefix of span fields that are recorded as a JSON object and logged as
+/// separate fields. See [`MozLogFormatLayer`].
+const SPREAD_FIELD_PREFIX: &str = "mozlog.spread.";
+
 /// This layer is exclusively concerned with formatting information using the
 /// [MozLog format](https://wiki.mozilla.org/Firefox/Services/Logging). It relies
 /// on the upstream [`crate::JsonStorageLayer`] to get access
@@ -22,19 +37,76 @@ const MOZLOG_VERSION: &str = "2.0";
 ///     .with(JsonStorageLayer)
 ///     .with(MozLogFormatLayer::new("service-name", std::io::stdout));
 /// ```
+///
+/// # JSON span fields
+///
+/// Span fields must be named when the span is created and can't be nested, so
+/// two prefixes mark span fields that are recorded as JSON strings:
+///
+/// - A field named `mozlog.json.<name>` is parsed and logged as `<name>`, so
+///   that it can hold a nested object.
+/// - A field named `mozlog.spread.<group>` must hold a JSON object, each entry
+///   of which is logged as a separate field. Fields set directly take
+///   precedence over these, and earlier groups in alphabetical order take
+///   precedence over later ones.
+///
+/// Fields with these prefixes that aren't valid JSON are logged unchanged.
+/// [`crate::MozLog`] uses them for its structured error, default fields, and
+/// captured response headers.
 pub struct MozLogFormatLayer<W: for<'a> MakeWriter<'a> + 'static> {
     name: String,
     pid: u32,
     hostname: String,
     make_writer: W,
+    type_required_for_level: Option<Level>,
+    app_version: Option<String>,
+    structured_spans: bool,
+    event_callback: Option<EventCallback>,
+    fields_key: Option<String>,
+    syslog_sd_id: Option<String>,
+    #[cfg(feature = "dev-pretty-print")]
+    pretty_print_levels: Vec<Level>,
+}
+
+/// A function called with each event logged by a [`MozLogFormatLayer`].
+type EventCallback = Arc<dyn Fn(&MozLogMessage) + Send + Sync>;
+
+/// A [`MozLogFormatLayer`] that only receives the events enabled by an [`EnvFilter`].
+pub type FilteredMozLogFormatLayer<W, S> = Filtered<MozLogFormatLayer<W>, EnvFilter, S>;
+
+/// How often a log file written by [`MozLogFormatLayer::with_file_writer`] is
+/// rotated. This mirrors [`tracing_appender::rolling::Rotation`].
+#[cfg(feature = "file-log")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rotation {
+    /// Start a new file every minute.
+    Minutely,
+    /// Start a new file every hour.
+    Hourly,
+    /// Start a new file every day.
+    Daily,
+    /// Always write to the same file.
+    Never,
+}
+
+#[cfg(feature = "file-log")]
+impl From<Rotation> for tracing_appender::rolling::Rotation {
+    fn from(rotation: Rotation) -> Self {
+        match rotation {
+            Rotation::Minutely => Self::MINUTELY,
+            Rotation::Hourly => Self::HOURLY,
+            Rotation::Daily => Self::DAILY,
+            Rotation::Never => Self::NEVER,
+        }
+    }
 }
 
 /// A logging message in MozLog format, adapted to Tracing.
-#[derive(Clone, Default, Debug, PartialEq, Deserialize, Serialize)]
+#[derive(Clone, Default, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MozLogMessage {
     /// Number of nanoseconds since the UNIX epoch (which is UTC)
-    pub timestamp: i64,
+    pub timestamp: u64,
 
     /// Type of message i.e. "request.summary"
     #[serde(rename = "type")]
@@ -59,6 +131,36 @@ pub struct MozLogMessage {
     pub fields: HashMap<String, Value>,
 }
 
+impl From<MozLogMessage> for HashMap<String, Value> {
+    /// Flatten a message into a single map, for log pipelines that don't
+    /// handle nested fields.
+    ///
+    /// The envelope fields are stored under `timestamp`, `type`, `logger`,
+    /// `hostname`, `env_version`, `pid` and `severity`, alongside the entries
+    /// of [`MozLogMessage::fields`]. Any field with the same name as an
+    /// envelope field is stored with a `fields.` prefix instead.
+    fn from(message: MozLogMessage) -> Self {
+        let mut map = HashMap::with_capacity(message.fields.len() + 7);
+        map.insert("timestamp".to_string(), message.timestamp.into());
+        map.insert("type".to_string(), message.message_type.into());
+        map.insert("logger".to_string(), message.logger.into());
+        map.insert("hostname".to_string(), message.hostname.into());
+        map.insert("env_version".to_string(), message.env_version.into());
+        map.insert("pid".to_string(), message.pid.into());
+        map.insert("severity".to_string(), message.severity.into());
+
+        for (key, value) in message.fields {
+            if map.contains_key(&key) {
+                map.insert(format!("fields.{}", key), value);
+            } else {
+                map.insert(key, value);
+            }
+        }
+
+        map
+    }
+}
+
 impl<W: for<'a> MakeWriter<'a> + 'static> MozLogFormatLayer<W> {
     /// Create a new moz log subscriber.
     pub fn new<S: AsRef<str>>(name: S, make_writer: W) -> Self {
@@ -67,12 +169,353 @@ impl<W: for<'a> MakeWriter<'a> + 'static> MozLogFormatLayer<W> {
             make_writer,
             pid: std::process::id(),
             hostname: gethostname().to_string_lossy().into_owned(),
+            type_required_for_level: None,
+            app_version: None,
+            structured_spans: false,
+            event_callback: None,
+            fields_key: None,
+            syslog_sd_id: None,
+            #[cfg(feature = "dev-pretty-print")]
+            pretty_print_levels: Vec::new(),
+        }
+    }
+
+    /// Drop events that don't have a `type` field if they are at `level` or
+    /// a more verbose level. For example, with `Some(Level::INFO)` untyped
+    /// `INFO`, `DEBUG`, and `TRACE` events are not logged, but untyped `WARN`
+    /// and `ERROR` events still are.
+    ///
+    /// This is useful to silence chatty libraries that don't follow MozLog
+    /// conventions. With `None`, the default, all events are logged.
+    pub fn with_type_required_for_level(mut self, level: Option<Level>) -> Self {
+        self.type_required_for_level = level;
+        self
+    }
+
+    /// Use the value of the environment variable `env_var`, such as
+    /// `WORKER_ID`, as the `Pid` of every event, if it is set to an integer.
+    /// Otherwise the process ID is kept.
+    ///
+    /// In containers the process ID is often `1`, which doesn't distinguish
+    /// between the processes of a service.
+    pub fn with_pid_from_env(mut self, env_var: &str) -> Self {
+        if let Some(pid) = std::env::var(env_var)
+            .ok()
+            .and_then(|value| value.trim().parse().ok())
+        {
+            self.pid = pid;
+        }
+        self
+    }
+
+    /// Add a `version` field with the deployed version of the service to
+    /// every event. Events or spans that record their own `version` field
+    /// take precedence.
+    pub fn with_app_version(mut self, version: impl Into<String>) -> Self {
+        self.app_version = Some(version.into());
+        self
+    }
+
+    /// Use the version from [`mozsvc_common::BuildInfo::from_env`] as the
+    /// app version, if build information is available.
+    ///
+    /// See [`with_app_version`](Self::with_app_version).
+    #[cfg(feature = "build-info")]
+    pub fn with_app_version_from_env(self) -> Self {
+        match mozsvc_common::BuildInfo::from_env() {
+            Some(info) if !info.version.is_empty() => self.with_app_version(info.version),
+            _ => self,
         }
     }
 
-    fn emit(&self, mut buffer: Vec<u8>) -> Result<(), std::io::Error> {
+    /// When enabled, the `spans` field is an array of span names, from the
+    /// outermost to the innermost span, instead of a comma-separated string.
+    /// This is disabled by default for compatibility with existing log
+    /// processing.
+    pub fn with_structured_spans(mut self, enabled: bool) -> Self {
+        self.structured_spans = enabled;
+        self
+    }
+
+    /// Call `callback` with each message just before it is written, such as to
+    /// count events for metrics. The callback runs on the thread that logged
+    /// the event, so it must not block.
+    pub fn with_event_callback(
+        mut self,
+        callback: Arc<dyn Fn(&MozLogMessage) + Send + Sync>,
+    ) -> Self {
+        self.event_callback = Some(callback);
+        self
+    }
+
+    /// Write the hash of arbitrary fields under `key` instead of MozLog's
+    /// `Fields`, for log schemas that use a different name. This only affects
+    /// the JSON output, not the [`MozLogMessage`] given to callbacks.
+    pub fn with_json_fields_key(mut self, key: impl Into<String>) -> Self {
+        self.fields_key = Some(key.into());
+        self
+    }
+
+    /// Write events as [RFC 5424] Syslog messages instead of JSON, for log
+    /// pipelines that only accept Syslog.
+    ///
+    /// The MozLog fields are written as the parameters of a structured data
+    /// element with the ID `mozlog@<enterprise_number>`, where
+    /// `enterprise_number` is the IANA Private Enterprise Number of the
+    /// organization running the service. The severity is written as the
+    /// severity of the PRI, with the "user-level messages" facility.
+    ///
+    /// [RFC 5424]: https://datatracker.ietf.org/doc/html/rfc5424
+    pub fn with_syslog_format(mut self, enterprise_number: u32) -> Self {
+        self.syslog_sd_id = Some(format!("mozlog@{}", enterprise_number));
+        self
+    }
+
+    /// Write events at any of `levels` as indented, multi-line JSON, so that
+    /// the most important events are easy to read during development. Events
+    /// at other levels are still written one per line.
+    ///
+    /// Most log collectors expect one event per line, so this is only
+    /// available with the `dev-pretty-print` feature.
+    #[cfg(feature = "dev-pretty-print")]
+    pub fn with_pretty_print_levels(mut self, levels: Vec<Level>) -> Self {
+        self.pretty_print_levels = levels;
+        self
+    }
+
+    /// Only format events that are enabled by `filter`.
+    ///
+    /// The filter applies only to this layer, so other layers registered with
+    /// the same subscriber will still see all events.
+    pub fn with_env_filter<S>(self, filter: EnvFilter) -> FilteredMozLogFormatLayer<W, S>
+    where
+        S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        self.with_filter(filter)
+    }
+
+    /// Only format events that are enabled by the directives in the `RUST_LOG`
+    /// environment variable.
+    pub fn with_default_env_filter<S>(self) -> FilteredMozLogFormatLayer<W, S>
+    where
+        S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        self.with_env_filter(EnvFilter::from_default_env())
+    }
+
+    /// Write to files named with `path_prefix` instead of this layer's
+    /// writer, starting a new file as often as `rotation` specifies.
+    ///
+    /// Rotated files have the date and time appended to the prefix, for
+    /// example `/var/log/service.log.2022-06-01` for daily rotation. The
+    /// directory containing `path_prefix` is created if it doesn't exist.
+    #[cfg(feature = "file-log")]
+    pub fn with_file_writer(
+        self,
+        path_prefix: &std::path::Path,
+        rotation: Rotation,
+    ) -> Result<MozLogFormatLayer<tracing_appender::rolling::RollingFileAppender>, std::io::Error>
+    {
+        let file_name = path_prefix.file_name().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "log path prefix must include a file name",
+            )
+        })?;
+        let directory = path_prefix
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or_else(|| std::path::Path::new("."));
+        let make_writer = tracing_appender::rolling::Builder::new()
+            .rotation(rotation.into())
+            .filename_prefix(file_name.to_string_lossy())
+            .build(directory)
+            .map_err(std::io::Error::other)?;
+
+        Ok(MozLogFormatLayer {
+            name: self.name,
+            pid: self.pid,
+            hostname: self.hostname,
+            make_writer,
+            type_required_for_level: self.type_required_for_level,
+            app_version: self.app_version,
+            structured_spans: self.structured_spans,
+            event_callback: self.event_callback,
+            fields_key: self.fields_key,
+            syslog_sd_id: self.syslog_sd_id,
+            #[cfg(feature = "dev-pretty-print")]
+            pretty_print_levels: self.pretty_print_levels,
+        })
+    }
+
+    /// Build the MozLog message for `event`, or `None` if the event should not
+    /// be logged.
+    pub(crate) fn format_event<S>(
+        &self,
+        event: &Event<'_>,
+        ctx: &Context<'_, S>,
+    ) -> Option<MozLogMessage>
+    where
+        S: Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        let mut event_visitor = MozLogVisitor::default();
+        event.record(&mut event_visitor);
+
+        let mut values: HashMap<String, Value> = event_visitor.into_values();
+
+        // Fields expanded from JSON span fields are added after every other
+        // field, so that they never take precedence.
+        let mut expanded_fields = vec![];
+        let spans = {
+            let mut span_names = vec![];
+            let mut current = ctx.lookup_current();
+            while let Some(span) = &current {
+                // Without a `JsonStorageLayer`, span fields aren't available.
+                if let Some(span_visitor) = span.extensions().get::<JsonStorage>() {
+                    for (k, v) in span_visitor.values() {
+                        match expand_json_field(k, v) {
+                            Some(expanded) => expanded_fields.push((*k, expanded)),
+                            None => {
+                                values
+                                    .entry(k.to_string())
+                                    .or_insert_with(|| canonical_span_field(k, v));
+                            }
+                        }
+                    }
+                }
+
+                span_names.push(span.name());
+                current = span.parent();
+            }
+            span_names.reverse();
+            if self.structured_spans {
+                Value::from(span_names)
+            } else {
+                Value::from(span_names.join(","))
+            }
+        };
+
+        // See https://en.wikipedia.org/wiki/Syslog#Severity_levels
+        let severity = match *event.metadata().level() {
+            Level::ERROR => 3, // Syslog Error
+            Level::WARN => 4,  // Syslog Warning
+            Level::INFO => 5,  // Syslog Normal
+            Level::DEBUG => 6, // Syslog Informational
+            Level::TRACE => 7, // Syslog Debug
+        };
+
+        if let Some(version) = &self.app_version {
+            values
+                .entry("version".to_string())
+                .or_insert_with(|| Value::String(version.clone()));
+        }
+
+        // Sorted by the name of the JSON field, so that earlier groups take
+        // precedence over later ones with the same field names.
+        expanded_fields.sort_by_key(|(name, _)| *name);
+        for (k, v) in expanded_fields
+            .into_iter()
+            .flat_map(|(_, expanded)| expanded)
+        {
+            values.entry(k).or_insert(v);
+        }
+
+        let type_field = values.remove("type");
+        let raw_type_field = values.remove("r#type");
+        if type_field.is_none() && raw_type_field.is_none() {
+            if let Some(required_level) = self.type_required_for_level {
+                if *event.metadata().level() >= required_level {
+                    return None;
+                }
+            }
+        }
+        values.insert("spans".to_string(), spans);
+
+        Some(MozLogMessage {
+            timestamp: SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+                .try_into()
+                .unwrap_or(u64::MAX),
+            message_type: type_field
+                .or(raw_type_field)
+                .and_then(|v| v.as_str().map(|s| s.to_string()))
+                .unwrap_or_else(|| "<unknown>".to_string()),
+            logger: self.name.clone(),
+            hostname: self.hostname.clone(),
+            env_version: MOZLOG_VERSION.to_string(),
+            pid: self.pid,
+            severity,
+            fields: values,
+        })
+    }
+
+    /// Write `message` as a line of JSON.
+    pub(crate) fn write_message(&self, message: &MozLogMessage, metadata: &Metadata<'_>) {
+        if let Some(callback) = &self.event_callback {
+            callback(message);
+        }
+
+        if let Some(sd_id) = &self.syslog_sd_id {
+            let log_line = crate::syslog::format_message(message, sd_id);
+            let _ = self.emit(log_line.into_bytes(), metadata);
+            return;
+        }
+
+        #[cfg(feature = "dev-pretty-print")]
+        let pretty = self.pretty_print_levels.contains(metadata.level());
+        #[cfg(not(feature = "dev-pretty-print"))]
+        let pretty = false;
+        let log_line = match &self.fields_key {
+            None => to_json(message, pretty),
+            Some(fields_key) => serde_json::to_value(message).and_then(|mut value| {
+                if let Some(object) = value.as_object_mut() {
+                    if let Some(fields) = object.remove("Fields") {
+                        object.insert(fields_key.clone(), fields);
+                    }
+                }
+                to_json(&value, pretty)
+            }),
+        };
+        // If there is an error, just squash it quietly. After all, if we
+        // failed to log, we can't exactly log an error.
+        if let Ok(log_line) = log_line {
+            let _ = self.emit(log_line, metadata);
+        }
+    }
+
+    fn emit(&self, mut buffer: Vec<u8>, metadata: &Metadata<'_>) -> Result<(), std::io::Error> {
         buffer.write_all(b"\n")?;
-        self.make_writer.make_writer().write_all(&buffer)
+        self.make_writer
+            .make_writer_for(metadata)
+            .write_all(&buffer)
+    }
+}
+
+/// Serialize `value` as JSON, indented over multiple lines if `pretty` is set.
+fn to_json<T: Serialize>(value: &T, pretty: bool) -> serde_json::Result<Vec<u8>> {
+    if pretty {
+        serde_json::to_vec_pretty(value)
+    } else {
+        serde_json::to_vec(value)
+    }
+}
+
+/// Expand a span field that was recorded as JSON, since span fields can't be
+/// named or nested at runtime. Returns `None` for other fields, which are
+/// logged as they are.
+fn expand_json_field(name: &str, value: &Value) -> Option<serde_json::Map<String, Value>> {
+    if let Some(name) = name.strip_prefix(JSON_FIELD_PREFIX) {
+        let value: Value = serde_json::from_str(value.as_str()?).ok()?;
+        Some(std::iter::once((name.to_string(), value)).collect())
+    } else if name.starts_with(SPREAD_FIELD_PREFIX) {
+        match serde_json::from_str(value.as_str()?).ok()? {
+            Value::Object(fields) => Some(fields),
+            _ => None,
+        }
+    } else {
+        None
     }
 }
 
@@ -82,78 +525,107 @@ where
     W: for<'a> MakeWriter<'a> + 'static,
 {
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        // Use a closure that returns a `Result` to enable usage of the `?`
-        // operator and make clearer code. This is called immediately below.
-        let make_log_line = || {
-            let mut event_visitor = JsonStorage::default();
-            event.record(&mut event_visitor);
-
-            let mut values: HashMap<String, Value> = event_visitor
-                .values()
-                .iter()
-                .map(|(k, v)| (k.to_string(), v.clone()))
-                .collect();
-
-            let spans = {
-                let mut span_names = vec![];
-                let mut current = ctx.lookup_current();
-                while let Some(span) = &current {
-                    {
-                        let ext = span.extensions();
-                        let span_visitor = ext
-                            .get::<JsonStorage>()
-                            .expect("MozLogFormatLayer requires JsonStorage layer");
-                        for (k, v) in span_visitor.values() {
-                            values.entry(k.to_string()).or_insert_with(|| v.clone());
-                        }
-                    }
+        if let Some(message) = self.format_event(event, &ctx) {
+            self.write_message(&message, event.metadata());
+        }
+    }
+}
+
+/// The value of the span field `name`, with the canonical MozLog fields `errno`
+/// and `msg` converted to an integer and a string like [`MozLogVisitor`] does
+/// for events. Span fields are recorded by [`crate::JsonStorageLayer`], so
+/// they can only be converted once they have been stored as JSON.
+fn canonical_span_field(name: &str, value: &Value) -> Value {
+    match (name, value) {
+        ("errno", Value::String(errno)) => errno
+            .trim()
+            .parse::<i64>()
+            .map_or_else(|_| value.clone(), Value::from),
+        ("msg", Value::String(_)) => value.clone(),
+        ("msg", value) => Value::String(value.to_string()),
+        _ => value.clone(),
+    }
+}
+
+/// Collects the fields of an event like [`JsonStorage`], except that the
+/// canonical MozLog fields `errno` and `msg` are always recorded as an integer
+/// and a string, respectively, regardless of how they were passed to Tracing.
+#[derive(Default)]
+struct MozLogVisitor<'a> {
+    storage: JsonStorage<'a>,
+    canonical: HashMap<&'static str, Value>,
+}
 
-                    span_names.push(span.name());
-                    current = span.parent();
+impl MozLogVisitor<'_> {
+    fn into_values(self) -> HashMap<String, Value> {
+        let mut values: HashMap<String, Value> = self
+            .storage
+            .values()
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect();
+        values.extend(self.canonical.into_iter().map(|(k, v)| (k.to_string(), v)));
+        values
+    }
+
+    /// Record `value` as a canonical field if `field` is one. Returns false if
+    /// the value should be recorded normally instead.
+    fn record_canonical(&mut self, field: &Field, value: &str) -> bool {
+        match field.name() {
+            "errno" => match value.trim().parse::<i64>() {
+                Ok(errno) => {
+                    self.canonical.insert("errno", errno.into());
+                    true
                 }
-                span_names.reverse();
-                span_names.join(",")
-            };
-
-            // See https://en.wikipedia.org/wiki/Syslog#Severity_levels
-            let severity = match *event.metadata().level() {
-                Level::ERROR => 3, // Syslog Error
-                Level::WARN => 4,  // Syslog Warning
-                Level::INFO => 5,  // Syslog Normal
-                Level::DEBUG => 6, // Syslog Informational
-                Level::TRACE => 7, // Syslog Debug
-            };
-
-            let type_field = values.remove("type");
-            let raw_type_field = values.remove("r#type");
-            values.insert("spans".to_string(), spans.into());
-
-            let v = MozLogMessage {
-                timestamp: SystemTime::now()
-                    .duration_since(SystemTime::UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_nanos() as i64,
-                message_type: type_field
-                    .or(raw_type_field)
-                    .and_then(|v| v.as_str().map(|s| s.to_string()))
-                    .unwrap_or_else(|| "<unknown>".to_string()),
-                logger: self.name.clone(),
-                hostname: self.hostname.clone(),
-                env_version: MOZLOG_VERSION.to_string(),
-                pid: self.pid,
-                severity,
-                fields: values,
-            };
-
-            // If there is an error, just squash it quietly. After all, if we
-            // failed to log, we can't exactly log an error.
-            serde_json::to_vec(&v).map_err(|_| ())
-        };
+                Err(_) => false,
+            },
+            "msg" => {
+                self.canonical.insert("msg", value.into());
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Visit for MozLogVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if !self.record_canonical(field, &value.to_string()) {
+            self.storage.record_i64(field, value);
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if !self.record_canonical(field, &value.to_string()) {
+            self.storage.record_u64(field, value);
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if field.name() == "msg" {
+            self.record_canonical(field, &value.to_string());
+        } else {
+            self.storage.record_f64(field, value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == "msg" {
+            self.record_canonical(field, &value.to_string());
+        } else {
+            self.storage.record_bool(field, value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if !self.record_canonical(field, value) {
+            self.storage.record_str(field, value);
+        }
+    }
 
-        let log_line_result: Result<Vec<u8>, ()> = make_log_line();
-        // Discard any errors, since they probably can't be logged anyways.
-        if let Ok(log_line) = log_line_result {
-            let _ = self.emit(log_line);
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if !self.record_canonical(field, &format!("{:?}", value)) {
+            self.storage.record_debug(field, value);
         }
     }
 }
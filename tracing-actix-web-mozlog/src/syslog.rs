@@ -0,0 +1,187 @@
+//! Formatting of MozLog messages as [RFC 5424] Syslog messages, for log
+//! pipelines that don't accept JSON.
+//!
+//! [RFC 5424]: https://datatracker.ietf.org/doc/html/rfc5424
+
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use crate::MozLogMessage;
+
+/// The Syslog facility of all messages, "user-level messages".
+const FACILITY: u32 = 1;
+
+/// Format `message` as an RFC 5424 message, with the MozLog fields as the
+/// parameters of a structured data element with the ID `sd_id`.
+///
+/// The header is filled in from the message: the logger is the APP-NAME, the
+/// PID is the PROCID, and the type is the MSGID. A string `message` field is
+/// used as the MSG instead of as a parameter, with control characters escaped
+/// so that each message stays on a single line.
+pub(crate) fn format_message(message: &MozLogMessage, sd_id: &str) -> String {
+    let mut line = format!(
+        "<{}>1 {} {} {} {} {} [{}",
+        FACILITY * 8 + message.severity,
+        format_timestamp(message.timestamp),
+        header_field(&message.hostname, 255),
+        header_field(&message.logger, 48),
+        message.pid,
+        header_field(&message.message_type, 32),
+        sd_id,
+    );
+
+    let mut fields: Vec<_> = message
+        .fields
+        .iter()
+        .filter(|(name, value)| name.as_str() != "message" || !value.is_string())
+        .collect();
+    fields.sort_by_key(|(name, _)| name.as_str());
+    for (name, value) in fields {
+        let value = match value {
+            Value::String(value) => value.clone(),
+            value => value.to_string(),
+        };
+        let _ = write!(line, " {}=\"{}\"", param_name(name), param_value(&value));
+    }
+    line.push(']');
+
+    if let Some(Value::String(text)) = message.fields.get("message") {
+        line.push(' ');
+        for c in text.chars() {
+            push_escaping_control(&mut line, c);
+        }
+    }
+    line
+}
+
+/// Format nanoseconds since the UNIX epoch as an RFC 3339 timestamp in UTC,
+/// with the microsecond precision that RFC 5424 allows.
+fn format_timestamp(timestamp: u64) -> String {
+    let seconds = timestamp / 1_000_000_000;
+    let micros = (timestamp % 1_000_000_000) / 1_000;
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let seconds_of_day = seconds % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+        micros,
+    )
+}
+
+/// Convert a number of days since the UNIX epoch to a year, month and day,
+/// using Howard Hinnant's `civil_from_days` algorithm.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// A header field, which must be printable ASCII without spaces, and no
+/// longer than `max_len`. Empty values are replaced with the NILVALUE `-`.
+fn header_field(value: &str, max_len: usize) -> String {
+    let field: String = value
+        .chars()
+        .map(|c| if c.is_ascii_graphic() { c } else { '_' })
+        .take(max_len)
+        .collect();
+    if field.is_empty() {
+        "-".to_string()
+    } else {
+        field
+    }
+}
+
+/// A structured data parameter name, which has the same restrictions as a
+/// header field, and also can't contain `=`, `]` or `"`.
+fn param_name(name: &str) -> String {
+    header_field(name, 32).replace(['=', ']', '"'], "_")
+}
+
+/// A structured data parameter value, with `"`, `\` and `]` escaped, as well
+/// as control characters.
+fn param_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '"' | '\\' | ']') {
+            escaped.push('\\');
+        }
+        push_escaping_control(&mut escaped, c);
+    }
+    escaped
+}
+
+/// Push `c` to `line`, replacing control characters such as newlines with
+/// their Rust escape sequences, such as `\n`.
+fn push_escaping_control(line: &mut String, c: char) {
+    if c.is_control() {
+        line.extend(c.escape_default());
+    } else {
+        line.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::{format_message, format_timestamp, param_value};
+    use crate::MozLogMessage;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00.000000Z");
+        assert_eq!(
+            format_timestamp(1_709_251_199_123_456_789),
+            "2024-02-29T23:59:59.123456Z"
+        );
+    }
+
+    #[test]
+    fn escaped_values() {
+        assert_eq!(param_value(r#"say "hi" [\o/]"#), r#"say \"hi\" [\\o/\]"#);
+        assert_eq!(param_value("two\nlines"), r"two\nlines");
+    }
+
+    #[test]
+    fn control_characters_in_message() {
+        let message = MozLogMessage {
+            message_type: "test".to_string(),
+            logger: "test-logger".to_string(),
+            hostname: "localhost".to_string(),
+            pid: 1,
+            severity: 5,
+            fields: HashMap::from([(
+                "message".to_string(),
+                json!("first line\nsecond line\r\tend"),
+            )]),
+            ..MozLogMessage::default()
+        };
+        let line = format_message(&message, "mozlog@32473");
+        assert!(!line.contains(['\n', '\r', '\t']), "{}", line);
+        assert!(
+            line.ends_with(r"] first line\nsecond line\r\tend"),
+            "{}",
+            line
+        );
+    }
+}
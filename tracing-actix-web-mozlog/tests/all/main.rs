@@ -1,4 +1,12 @@
+mod test_builder;
+mod test_concurrency;
+#[cfg(feature = "file-log")]
+mod test_file_writer;
 mod test_json_schema;
+#[cfg(feature = "log-compat")]
+mod test_log_compat;
 mod test_middleware;
 mod test_mozlog_fields;
+#[cfg(feature = "opentelemetry")]
+mod test_otel;
 mod utils;
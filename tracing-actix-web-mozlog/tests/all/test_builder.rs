@@ -0,0 +1,58 @@
+use serde_json::json;
+use tracing::{event, span, Level};
+use tracing_actix_web_mozlog::MozLogSubscriberBuilder;
+use tracing_subscriber::{fmt::MakeWriter, EnvFilter};
+
+use crate::utils::LogWatcher;
+
+#[test]
+fn builder_default_subscriber() {
+    let subscriber = MozLogSubscriberBuilder::new("svc").build();
+    tracing::subscriber::with_default(subscriber, || {
+        let _guard = span!(Level::INFO, "test_span", color = "red").entered();
+        event!(Level::INFO, "test_event");
+    });
+}
+
+#[test]
+fn builder_options() {
+    let mut log_watcher: LogWatcher = LogWatcher::default();
+    let log_watcher_writer = log_watcher.make_writer();
+    let subscriber = MozLogSubscriberBuilder::new("svc")
+        .with_service_name("test-logger")
+        .with_writer(move || log_watcher_writer.clone())
+        .with_env_filter(EnvFilter::new("info"))
+        .with_type_required(true)
+        .build();
+    tracing::subscriber::with_default(subscriber, || {
+        let _guard = span!(Level::INFO, "test_span", color = "red").entered();
+        event!(Level::DEBUG, r#type = "test", "filtered out");
+        event!(Level::ERROR, "untyped");
+        event!(Level::INFO, r#type = "test", "logged");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].logger, "test-logger");
+    assert_eq!(events[0].fields.get("message"), Some(&json!("logged")));
+    assert_eq!(events[0].fields.get("color"), Some(&json!("red")));
+}
+
+#[test]
+fn builder_without_json_storage() {
+    let mut log_watcher: LogWatcher = LogWatcher::default();
+    let log_watcher_writer = log_watcher.make_writer();
+    let subscriber = MozLogSubscriberBuilder::new("test-logger")
+        .with_writer(move || log_watcher_writer.clone())
+        .with_json_storage(false)
+        .build();
+    tracing::subscriber::with_default(subscriber, || {
+        let _guard = span!(Level::INFO, "test_span", color = "red").entered();
+        event!(Level::INFO, "test_event");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].fields.get("color"), None);
+    assert_eq!(events[0].fields.get("spans"), Some(&json!("test_span")));
+}
@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use tracing::{event, Level};
+use tracing_futures::WithSubscriber;
+
+use crate::utils::{log_test_dispatch, LogWatcher};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_events_are_not_interleaved() {
+    let (mut log_watcher, dispatch): (LogWatcher, _) = log_test_dispatch();
+
+    let tasks: Vec<_> = (0..100)
+        .map(|task| {
+            tokio::spawn(
+                async move {
+                    tokio::task::yield_now().await;
+                    event!(
+                        Level::INFO,
+                        task,
+                        r#type = "test.concurrent",
+                        "concurrent event"
+                    );
+                }
+                .with_subscriber(dispatch.clone()),
+            )
+        })
+        .collect();
+    for task in tasks {
+        task.await.expect("task panicked");
+    }
+
+    log_watcher.assert_schema_valid();
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 100);
+    let tasks: HashSet<_> = events
+        .iter()
+        .map(|event| {
+            event.fields["task"]
+                .as_u64()
+                .expect("task should be a number")
+        })
+        .collect();
+    assert_eq!(tasks, (0..100).collect());
+    assert!(events
+        .iter()
+        .all(|event| event.fields["message"] == json!("concurrent event")));
+}
@@ -0,0 +1,54 @@
+use std::{fs, io::BufRead};
+
+use tracing::{event, Level};
+use tracing_actix_web_mozlog::{JsonStorageLayer, MozLogFormatLayer, MozLogMessage, Rotation};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use crate::utils::MOZLOG_SCHEMA;
+
+#[test]
+fn file_writer_writes_mozlog() {
+    let directory = std::env::temp_dir().join(format!("mozlog-file-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+
+    let layer = MozLogFormatLayer::new("test-logger", std::io::sink)
+        .with_file_writer(&directory.join("service.log"), Rotation::Daily)
+        .expect("could not create file writer");
+    let subscriber = Registry::default().with(JsonStorageLayer).with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        event!(Level::INFO, r#type = "test", "first");
+        event!(Level::WARN, r#type = "test", "second");
+    });
+
+    let files: Vec<_> = fs::read_dir(&directory)
+        .expect("log directory should exist")
+        .map(|entry| entry.expect("could not read directory entry").path())
+        .collect();
+    assert_eq!(
+        files.len(),
+        1,
+        "expected a single log file, got {:?}",
+        files
+    );
+    let file_name = files[0].file_name().unwrap().to_string_lossy().into_owned();
+    assert!(
+        file_name.starts_with("service.log."),
+        "unexpected file name {}",
+        file_name
+    );
+
+    let contents = fs::read(&files[0]).expect("could not read log file");
+    let lines: Vec<String> = contents.lines().map(Result::unwrap).collect();
+    assert_eq!(lines.len(), 2);
+    for (line, expected_message) in lines.iter().zip(["first", "second"]) {
+        let value: serde_json::Value = serde_json::from_str(line).expect("line is not JSON");
+        assert!(MOZLOG_SCHEMA.is_valid(&value), "invalid MozLog: {}", line);
+        let message: MozLogMessage = serde_json::from_value(value).unwrap();
+        assert_eq!(message.logger, "test-logger");
+        assert_eq!(message.pid, std::process::id());
+        assert_eq!(message.message_type, "test");
+        assert_eq!(message.fields["message"], expected_message);
+    }
+
+    fs::remove_dir_all(&directory).expect("could not clean up log directory");
+}
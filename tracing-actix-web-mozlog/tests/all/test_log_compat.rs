@@ -0,0 +1,54 @@
+use maplit::hashmap;
+use pretty_assertions::assert_eq;
+use serde_json::json;
+use tracing_actix_web_mozlog::{init_log_compat, MozLogMessage};
+
+use crate::utils::{log_test, LogWatcher};
+
+#[test]
+fn log_records_are_logged() {
+    init_log_compat().expect("could not set the logger");
+
+    let mut log_watcher: LogWatcher = log_test(|| {
+        log::warn!(target: "legacy", "disk {} is {}% full", "sda", 90);
+    });
+
+    let event = log_watcher.first_or_panic("should log an event");
+    assert_eq!(event.severity, 4);
+    assert_eq!(
+        event.fields.get("message"),
+        Some(&json!("disk sda is 90% full"))
+    );
+    assert_eq!(event.fields.get("log.target"), Some(&json!("legacy")));
+}
+
+#[test]
+fn message_as_log_record() {
+    let message = MozLogMessage {
+        message_type: "user.login".to_string(),
+        severity: 3,
+        fields: hashmap! {
+            "message".to_string() => json!("login failed"),
+            "uid".to_string() => json!("abc"),
+            "attempts".to_string() => json!(3),
+        },
+        ..MozLogMessage::default()
+    };
+
+    message.with_log_record(|record| {
+        assert_eq!(record.level(), log::Level::Error);
+        assert_eq!(record.target(), "user.login");
+        assert_eq!(record.args().to_string(), "login failed");
+
+        let key_values = record.key_values();
+        assert_eq!(key_values.count(), 2);
+        assert_eq!(
+            key_values.get("uid".into()).map(|v| v.to_string()),
+            Some("abc".to_string())
+        );
+        assert_eq!(
+            key_values.get("attempts".into()).map(|v| v.to_string()),
+            Some("3".to_string())
+        );
+    });
+}
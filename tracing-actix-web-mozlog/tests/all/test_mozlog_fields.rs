@@ -1,9 +1,24 @@
-use crate::utils::{log_test, LogWatcher};
+use crate::utils::{log_test, log_test_dispatch, log_test_with_layer, LogWatcher};
 use maplit::hashmap;
 use pretty_assertions::assert_eq;
-use serde_json::json;
-use tracing::{event, span, Level};
-use tracing_actix_web_mozlog::MozLogMessage;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    io::Write,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tracing::{
+    callsite::{Callsite, Identifier},
+    event,
+    field::{Field, FieldSet},
+    metadata::Kind,
+    span,
+    subscriber::Interest,
+    Level, Metadata, Span,
+};
+use tracing_actix_web_mozlog::{JsonStorageLayer, MozLogFormatLayer, MozLogMessage};
+use tracing_futures::{Instrument, WithSubscriber};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
 
 #[test]
 fn test_format() {
@@ -44,7 +59,7 @@ fn test_format() {
     // magnitude, the below would fail. Gigaseconds are 1x10^9 seconds. 1
     // gigaseconds since epoch is sometime in the year 2001, and 4 gigaseconds
     // is in 2096.
-    let gigaseconds = events[0].timestamp / i64::pow(10, 18);
+    let gigaseconds = events[0].timestamp / u64::pow(10, 18);
     assert!(
         (1..=4).contains(&gigaseconds),
         "Should have a timestamp in this century"
@@ -106,6 +121,70 @@ fn test_span_is_listed() {
     );
 }
 
+#[test]
+fn test_top_level_event_has_empty_spans() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        event!(Level::INFO, r#type = "test", "top level event");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].fields["spans"], json!(""));
+}
+
+#[test]
+fn test_multiple_events_outside_span() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        event!(Level::ERROR, "error");
+        event!(Level::WARN, "warn");
+        // A span that has been exited doesn't enclose later events.
+        span!(Level::INFO, "exited_span").in_scope(|| {});
+        event!(Level::INFO, "info");
+        event!(Level::DEBUG, "debug");
+        event!(Level::TRACE, "trace");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 5);
+    for event in events {
+        assert_eq!(event.fields["spans"], json!(""), "{:?}", event);
+    }
+}
+
+#[test]
+fn events_of_type_and_severity() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        event!(Level::INFO, r#type = "request.summary", "first summary");
+        event!(Level::WARN, r#type = "auth.failure", "failure");
+        event!(Level::INFO, "untyped");
+        event!(Level::ERROR, r#type = "request.summary", "second summary");
+    });
+
+    let messages = |events: Vec<&MozLogMessage>| -> Vec<Value> {
+        events
+            .into_iter()
+            .map(|event| event.fields["message"].clone())
+            .collect()
+    };
+    assert_eq!(
+        messages(log_watcher.events_of_type("request.summary")),
+        vec![json!("first summary"), json!("second summary")]
+    );
+    assert_eq!(
+        messages(log_watcher.events_of_type("<unknown>")),
+        vec![json!("untyped")]
+    );
+    assert!(log_watcher.events_of_type("other").is_empty());
+    assert_eq!(
+        messages(log_watcher.events_of_severity(5)),
+        vec![json!("first summary"), json!("untyped")]
+    );
+    assert_eq!(
+        messages(log_watcher.events_of_severity(3)),
+        vec![json!("second summary")]
+    );
+}
+
 #[test]
 fn test_nested_spans() {
     let mut log_watcher: LogWatcher = log_test(|| {
@@ -116,12 +195,12 @@ fn test_nested_spans() {
         event!(Level::INFO, "event at nesting 2");
     });
 
-    let events = log_watcher.events();
+    let events: Vec<MozLogMessage> = log_watcher.events_sorted().into_iter().cloned().collect();
     assert!(!events.is_empty());
 
     assert_eq!(
         events,
-        &vec![
+        vec![
             MozLogMessage {
                 fields: hashmap!(
                     "message".to_string() => "event at nesting 0".into(),
@@ -193,3 +272,689 @@ fn innermost_value_wins() {
         }]
     );
 }
+
+#[test]
+fn deeply_nested_field_inheritance() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let _level1 = span!(Level::INFO, "level1", field1 = 1, depth = 1).entered();
+        let _level2 = span!(Level::INFO, "level2", field2 = 2, depth = 2).entered();
+        let _level3 = span!(Level::INFO, "level3", field3 = 3, depth = 3).entered();
+        let _level4 = span!(Level::INFO, "level4", field4 = 4, depth = 4).entered();
+        let _level5 = span!(Level::INFO, "level5", field5 = 5, depth = 5).entered();
+        event!(Level::INFO, "test_event");
+    });
+    let events = log_watcher.events();
+    assert!(!events.is_empty());
+
+    assert_eq!(
+        events,
+        &vec![MozLogMessage {
+            fields: hashmap!(
+                "message".to_string() => "test_event".into(),
+                "spans".to_string() => "level1,level2,level3,level4,level5".into(),
+                "field1".to_string() => 1.into(),
+                "field2".to_string() => 2.into(),
+                "field3".to_string() => 3.into(),
+                "field4".to_string() => 4.into(),
+                "field5".to_string() => 5.into(),
+                "depth".to_string() => 5.into(),
+            ),
+            ..events[0].clone()
+        }]
+    );
+}
+
+#[test]
+fn dotted_error_fields_are_unchanged() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let _guard = span!(Level::INFO, "test_span", "error.kind" = "timeout").entered();
+        event!(Level::WARN, "error.code" = 504, "test_event");
+    });
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].fields.get("error.kind"), Some(&json!("timeout")));
+    assert_eq!(events[0].fields.get("error.code"), Some(&json!(504)));
+    assert_eq!(events[0].fields.get("error"), None);
+}
+
+#[test]
+fn unprefixed_json_span_fields_are_unchanged() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let _guard = span!(
+            Level::INFO,
+            "request",
+            default_fields = r#"{"region":"us-west"}"#,
+            response_headers = r#"{"etag":"abc"}"#
+        )
+        .entered();
+        event!(Level::INFO, "test_event");
+    });
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(
+        events[0].fields.get("default_fields"),
+        Some(&json!(r#"{"region":"us-west"}"#)),
+        "only fields with a JSON prefix should be expanded"
+    );
+    assert_eq!(events[0].fields.get("region"), None);
+    assert_eq!(
+        events[0].fields.get("response_headers"),
+        Some(&json!(r#"{"etag":"abc"}"#))
+    );
+    assert_eq!(events[0].fields.get("etag"), None);
+}
+
+#[test]
+fn json_span_fields_are_expanded() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let _outer = span!(
+            Level::INFO,
+            "outer",
+            "mozlog.spread.a" = r#"{"region":"us-west","shard":1}"#,
+            "mozlog.spread.b" = r#"{"region":"eu-west","zone":"b"}"#
+        )
+        .entered();
+        let _inner = span!(
+            Level::INFO,
+            "inner",
+            "mozlog.json.error" = r#"{"code":504}"#,
+            "mozlog.json.invalid" = "not json",
+            shard = 2
+        )
+        .entered();
+        event!(Level::INFO, "test_event");
+    });
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    let fields = &events[0].fields;
+    assert_eq!(fields.get("error"), Some(&json!({"code": 504})));
+    assert_eq!(
+        fields.get("region"),
+        Some(&json!("us-west")),
+        "earlier groups should take precedence"
+    );
+    assert_eq!(fields.get("zone"), Some(&json!("b")));
+    assert_eq!(
+        fields.get("shard"),
+        Some(&json!(2)),
+        "fields set directly should take precedence"
+    );
+    assert_eq!(
+        fields.get("mozlog.json.invalid"),
+        Some(&json!("not json")),
+        "invalid JSON should be logged unchanged"
+    );
+    assert_eq!(fields.get("mozlog.json.error"), None);
+    assert_eq!(fields.get("mozlog.spread.a"), None);
+}
+
+#[test]
+fn sibling_span_fields_are_isolated() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let span_a = span!(Level::INFO, "span_a", color = "red").entered();
+        span_a.exit();
+        let _span_b = span!(Level::INFO, "span_b", color = "blue").entered();
+        event!(Level::INFO, "test_event");
+    });
+    let events = log_watcher.events();
+    assert!(!events.is_empty());
+
+    assert_eq!(
+        events,
+        &vec![MozLogMessage {
+            fields: hashmap!(
+                "message".to_string() => "test_event".into(),
+                "spans".to_string() => "span_b".into(),
+                "color".to_string() => "blue".into(),
+            ),
+            ..events[0].clone()
+        }]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn span_entered_in_another_task() {
+    let (mut log_watcher, dispatch): (LogWatcher, _) = log_test_dispatch();
+    let (sender, receiver) = tokio::sync::oneshot::channel();
+
+    let task_a = tokio::spawn(
+        async move {
+            let span = span!(
+                Level::INFO,
+                "shared_span",
+                color = "red",
+                shape = tracing::field::Empty
+            );
+            span.record("shape", "circle");
+            sender.send(span).expect("receiver was dropped");
+        }
+        .with_subscriber(dispatch.clone()),
+    );
+    let task_b = tokio::spawn(
+        async move {
+            let span = receiver.await.expect("sender was dropped");
+            let _guard = span.enter();
+            event!(Level::INFO, r#type = "test", "event in another task");
+        }
+        .with_subscriber(dispatch),
+    );
+    task_a.await.expect("task A panicked");
+    task_b.await.expect("task B panicked");
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].fields["spans"], json!("shared_span"));
+    assert_eq!(events[0].fields["color"], json!("red"));
+    assert_eq!(events[0].fields["shape"], json!("circle"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn instrumented_task() {
+    let (mut log_watcher, dispatch): (LogWatcher, _) = log_test_dispatch();
+    let span = tracing::dispatcher::with_default(&dispatch, || {
+        span!(Level::INFO, "task_span", color = "green")
+    });
+
+    tokio::spawn(
+        async {
+            tokio::task::yield_now().await;
+            event!(
+                Level::INFO,
+                r#type = "test",
+                "event in an instrumented task"
+            );
+        }
+        .instrument(span)
+        .with_subscriber(dispatch),
+    )
+    .await
+    .expect("task panicked");
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].fields["spans"], json!("task_span"));
+    assert_eq!(events[0].fields["color"], json!("green"));
+}
+
+/// The number of fields on the span created by [`big_span`].
+const BIG_SPAN_FIELDS: usize = 200;
+
+/// A callsite for a span with [`BIG_SPAN_FIELDS`] fields named `field_0`,
+/// `field_1`, and so on. `span!` needs field names at compile time, so the
+/// metadata is built by hand.
+struct BigSpanCallsite;
+
+static BIG_SPAN_CALLSITE: BigSpanCallsite = BigSpanCallsite;
+static BIG_SPAN_METADATA: OnceLock<Metadata<'static>> = OnceLock::new();
+
+impl Callsite for BigSpanCallsite {
+    fn set_interest(&self, _interest: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        BIG_SPAN_METADATA.get_or_init(|| {
+            let names: Vec<&'static str> = (0..BIG_SPAN_FIELDS)
+                .map(|i| &*Box::leak(format!("field_{}", i).into_boxed_str()))
+                .collect();
+            Metadata::new(
+                "big_span",
+                module_path!(),
+                Level::INFO,
+                Some(file!()),
+                Some(line!()),
+                Some(module_path!()),
+                FieldSet::new(names.leak(), Identifier(&BIG_SPAN_CALLSITE)),
+                Kind::SPAN,
+            )
+        })
+    }
+}
+
+/// Create a span where the field `field_N` has the value `N`.
+fn big_span() -> Span {
+    let metadata = BIG_SPAN_CALLSITE.metadata();
+    let fields: Vec<Field> = metadata.fields().iter().collect();
+    let values: Vec<i64> = (0..BIG_SPAN_FIELDS as i64).collect();
+    let value_set: [(&Field, Option<&dyn tracing::Value>); BIG_SPAN_FIELDS] =
+        std::array::from_fn(|i| (&fields[i], Some(&values[i] as &dyn tracing::Value)));
+    Span::new(metadata, &metadata.fields().value_set(&value_set))
+}
+
+#[test]
+fn many_span_fields() {
+    let mut log_watcher: LogWatcher<Value> = log_test(|| {
+        let _guard = big_span().entered();
+        event!(Level::INFO, r#type = "test", "event in a big span");
+    });
+
+    log_watcher.assert_schema_valid();
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 1);
+    let fields = events[0]["Fields"]
+        .as_object()
+        .expect("fields should be an object");
+    for i in 0..BIG_SPAN_FIELDS {
+        assert_eq!(
+            fields.get(&format!("field_{}", i)),
+            Some(&json!(i)),
+            "field_{} should be logged",
+            i
+        );
+    }
+    // The span fields, and `message` and `spans`.
+    assert_eq!(fields.len(), BIG_SPAN_FIELDS + 2);
+    assert_eq!(fields["spans"], json!("big_span"));
+}
+
+#[test]
+fn env_filter() {
+    let mut log_watcher: LogWatcher = log_test_with_layer(
+        |layer| layer.with_env_filter(EnvFilter::new("warn")),
+        || {
+            event!(Level::DEBUG, "debug event");
+            event!(Level::WARN, "warn event");
+        },
+    );
+
+    let messages: Vec<_> = log_watcher
+        .events()
+        .iter()
+        .map(|event| event.fields.get("message").cloned())
+        .collect();
+    assert_eq!(messages, vec![Some(json!("warn event"))]);
+}
+
+#[test]
+fn event_callback() {
+    let received: Arc<Mutex<Vec<MozLogMessage>>> = Arc::default();
+    let callback_received = received.clone();
+
+    let mut log_watcher: LogWatcher<MozLogMessage> = log_test_with_layer(
+        |layer| {
+            layer.with_event_callback(Arc::new(move |message| {
+                callback_received.lock().unwrap().push(message.clone());
+            }))
+        },
+        || {
+            event!(Level::INFO, r#type = "first", "first event");
+            event!(Level::WARN, r#type = "second", "second event");
+        },
+    );
+
+    let received = received.lock().unwrap();
+    assert_eq!(*received, *log_watcher.events());
+    assert_eq!(received.len(), 2);
+    assert_eq!(received[0].message_type, "first");
+    assert_eq!(received[1].message_type, "second");
+}
+
+#[test]
+fn json_fields_key() {
+    let mut log_watcher: LogWatcher<Value> = log_test_with_layer(
+        |layer| layer.with_json_fields_key("Payload"),
+        || {
+            event!(Level::INFO, r#type = "test", color = "red", "custom key");
+        },
+    );
+
+    let event = log_watcher.first_or_panic("should log an event");
+    assert_eq!(event.get("Fields"), None);
+    assert_eq!(event["Payload"]["color"], json!("red"));
+    assert_eq!(event["Payload"]["message"], json!("custom key"));
+    assert_eq!(event["type"], json!("test"));
+}
+
+#[cfg(feature = "dev-pretty-print")]
+#[test]
+fn pretty_print_levels() {
+    let log_watcher: LogWatcher = log_test_with_layer(
+        |layer| layer.with_pretty_print_levels(vec![Level::ERROR, Level::WARN]),
+        || {
+            event!(Level::INFO, r#type = "test", "compact");
+            event!(Level::ERROR, r#type = "test", "pretty");
+        },
+    );
+
+    let output = log_watcher.raw_output();
+    let (compact, pretty) = output.split_once('\n').unwrap();
+    assert!(compact.contains("\"compact\""), "{}", compact);
+    assert!(
+        compact.starts_with('{') && compact.ends_with('}'),
+        "{}",
+        compact
+    );
+    assert!(pretty.contains("\"pretty\""), "{}", pretty);
+    assert!(pretty.starts_with("{\n  \""), "{}", pretty);
+    assert!(pretty.lines().count() > 10, "{}", pretty);
+
+    let event: MozLogMessage = serde_json::from_str(pretty).unwrap();
+    assert_eq!(event.fields.get("message"), Some(&json!("pretty")));
+}
+
+#[test]
+fn syslog_format() {
+    let log_watcher: LogWatcher = log_test_with_layer(
+        |layer| layer.with_syslog_format(32473),
+        || {
+            event!(
+                Level::WARN,
+                r#type = "user.login",
+                color = "red",
+                quote = r#"a "quoted" value"#,
+                count = 3,
+                "logged in"
+            );
+        },
+    );
+
+    let output = log_watcher.raw_output();
+    assert!(output.ends_with('\n'), "{}", output);
+    let line = output.trim_end();
+    let mut header = line.splitn(7, ' ');
+    assert_eq!(header.next(), Some("<12>1"), "PRI and VERSION");
+    let timestamp = header.next().unwrap();
+    assert_eq!(timestamp.len(), "2024-01-01T00:00:00.000000Z".len());
+    assert!(timestamp.ends_with('Z'), "{}", timestamp);
+    assert_eq!(timestamp.as_bytes()[10], b'T', "{}", timestamp);
+    let hostname = header.next().unwrap();
+    assert!(!hostname.is_empty() && hostname != "-");
+    assert_eq!(header.next(), Some("test-logger"), "APP-NAME");
+    assert_eq!(
+        header.next(),
+        Some(std::process::id().to_string().as_str()),
+        "PROCID"
+    );
+    assert_eq!(header.next(), Some("user.login"), "MSGID");
+    assert_eq!(
+        header.next(),
+        Some(
+            r#"[mozlog@32473 color="red" count="3" quote="a \"quoted\" value" spans=""] logged in"#
+        ),
+        "STRUCTURED-DATA and MSG"
+    );
+}
+
+#[test]
+fn errno_and_msg_have_canonical_types() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        event!(
+            Level::ERROR,
+            errno = 404u32,
+            msg = "not found",
+            "native types"
+        );
+        event!(Level::ERROR, errno = %404, msg = %"not found", "display values");
+        event!(Level::ERROR, errno = "404", msg = 404, "swapped types");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 3);
+    for event in events {
+        assert_eq!(event.fields.get("errno"), Some(&json!(404)), "{:?}", event);
+    }
+    assert_eq!(events[0].fields.get("msg"), Some(&json!("not found")));
+    assert_eq!(events[1].fields.get("msg"), Some(&json!("not found")));
+    assert_eq!(events[2].fields.get("msg"), Some(&json!("404")));
+}
+
+#[test]
+fn span_errno_and_msg_have_canonical_types() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let _outer = span!(Level::INFO, "outer", errno = %404, msg = 404).entered();
+        event!(Level::ERROR, "display errno and numeric msg");
+        let _inner = span!(Level::INFO, "inner", errno = "not-a-number", msg = true).entered();
+        event!(Level::ERROR, "non-numeric errno and boolean msg");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].fields.get("errno"), Some(&json!(404)));
+    assert_eq!(events[0].fields.get("msg"), Some(&json!("404")));
+    assert_eq!(
+        events[1].fields.get("errno"),
+        Some(&json!("not-a-number")),
+        "non-numeric values should be kept"
+    );
+    assert_eq!(events[1].fields.get("msg"), Some(&json!("true")));
+}
+
+#[test]
+fn non_numeric_errno_is_kept() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        event!(Level::ERROR, errno = "not-a-number", "bad errno");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events[0].fields.get("errno"), Some(&json!("not-a-number")));
+}
+
+#[test]
+fn type_required_for_level() {
+    let mut log_watcher: LogWatcher = log_test_with_layer(
+        |layer| layer.with_type_required_for_level(Some(Level::INFO)),
+        || {
+            event!(Level::INFO, "untyped info");
+            event!(Level::INFO, r#type = "test", "typed info");
+            event!(Level::WARN, "untyped warn");
+        },
+    );
+
+    let messages: Vec<_> = log_watcher
+        .events()
+        .iter()
+        .map(|event| event.fields.get("message").cloned())
+        .collect();
+    assert_eq!(
+        messages,
+        vec![Some(json!("typed info")), Some(json!("untyped warn"))]
+    );
+}
+
+#[test]
+fn events_sorted_by_timestamp_then_type() {
+    let first = MozLogMessage {
+        timestamp: 1,
+        message_type: "b".to_string(),
+        ..Default::default()
+    };
+    let second = MozLogMessage {
+        timestamp: 2,
+        message_type: "a".to_string(),
+        ..Default::default()
+    };
+    let third = MozLogMessage {
+        timestamp: 2,
+        message_type: "b".to_string(),
+        ..Default::default()
+    };
+    let fourth = MozLogMessage {
+        severity: 3,
+        ..third.clone()
+    };
+
+    let mut log_watcher: LogWatcher = LogWatcher::default();
+    let mut writer = log_watcher.make_writer();
+    for message in [&third, &first, &fourth, &second] {
+        serde_json::to_writer(&mut writer, message).unwrap();
+        writer.write_all(b"\n").unwrap();
+    }
+
+    let sorted: Vec<MozLogMessage> = log_watcher.events_sorted().into_iter().cloned().collect();
+    assert_eq!(sorted, vec![first, second, third, fourth]);
+}
+
+#[test]
+fn test_writer_per_level() {
+    use tracing_subscriber::fmt::writer::MakeWriterExt;
+
+    let mut error_watcher: LogWatcher<MozLogMessage> = LogWatcher::default();
+    let mut info_watcher: LogWatcher<MozLogMessage> = LogWatcher::default();
+    let make_writer = error_watcher
+        .make_writer_for_level(Level::ERROR)
+        .and(info_watcher.make_writer_for_level(Level::INFO));
+    let subscriber = Registry::default()
+        .with(JsonStorageLayer)
+        .with(MozLogFormatLayer::new("test-logger", make_writer));
+
+    tracing::subscriber::with_default(subscriber, || {
+        event!(Level::ERROR, r#type = "test", "an error");
+        event!(Level::INFO, r#type = "test", "some info");
+        event!(Level::WARN, r#type = "test", "a warning");
+    });
+
+    let errors = error_watcher.events();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].fields["message"], json!("an error"));
+
+    let infos = info_watcher.events();
+    assert_eq!(infos.len(), 1);
+    assert_eq!(infos[0].fields["message"], json!("some info"));
+}
+
+#[test]
+fn app_version() {
+    let mut log_watcher: LogWatcher = log_test_with_layer(
+        |layer| layer.with_app_version("1.2.3"),
+        || {
+            event!(Level::INFO, r#type = "test", "no span");
+            let _guard = span!(Level::INFO, "test_span").entered();
+            event!(Level::WARN, "in a span");
+            event!(Level::INFO, version = "override", "own version");
+        },
+    );
+
+    let versions: Vec<_> = log_watcher
+        .events()
+        .iter()
+        .map(|event| event.fields.get("version").cloned())
+        .collect();
+    assert_eq!(
+        versions,
+        vec![
+            Some(json!("1.2.3")),
+            Some(json!("1.2.3")),
+            Some(json!("override"))
+        ]
+    );
+}
+
+#[test]
+fn structured_spans() {
+    let mut log_watcher: LogWatcher = log_test_with_layer(
+        |layer| layer.with_structured_spans(true),
+        || {
+            let _outer = span!(Level::INFO, "outer").entered();
+            let _inner = span!(Level::INFO, "inner").entered();
+            event!(Level::INFO, r#type = "test", "nested");
+        },
+    );
+
+    let events = log_watcher.events();
+    assert_eq!(
+        events[0].fields.get("spans"),
+        Some(&Value::Array(vec!["outer".into(), "inner".into()]))
+    );
+}
+
+#[test]
+fn timestamp_round_trips_exactly() {
+    // Larger than 2^53, so this would lose precision as a float.
+    let json = r#"{
+        "Timestamp": 1654041600123456789,
+        "type": "test",
+        "Logger": "test-logger",
+        "Hostname": "localhost",
+        "EnvVersion": "2.0",
+        "Pid": 1,
+        "Severity": 5,
+        "Fields": {}
+    }"#;
+    let message: MozLogMessage = serde_json::from_str(json).expect("could not deserialize");
+    assert_eq!(message.timestamp, 1_654_041_600_123_456_789);
+
+    let value = serde_json::to_value(&message).expect("could not serialize");
+    assert_eq!(value["Timestamp"].as_u64(), Some(1_654_041_600_123_456_789));
+    let round_tripped: MozLogMessage =
+        serde_json::from_value(value).expect("could not deserialize");
+    assert_eq!(round_tripped, message);
+}
+
+#[test]
+fn span_fields_recorded_after_creation() {
+    let mut log_watcher: LogWatcher = log_test(|| {
+        let span = span!(
+            Level::INFO,
+            "test_span",
+            color = "red",
+            shape = tracing::field::Empty
+        );
+        let _guard = span.enter();
+        event!(Level::INFO, r#type = "test", "before record");
+        span.record("shape", "circle");
+        span.record("color", "blue");
+        event!(Level::INFO, r#type = "test", "after record");
+    });
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].fields.get("color"), Some(&json!("red")));
+    assert_eq!(events[0].fields.get("shape"), None);
+    assert_eq!(events[1].fields.get("color"), Some(&json!("blue")));
+    assert_eq!(events[1].fields.get("shape"), Some(&json!("circle")));
+}
+
+#[test]
+fn flatten_message() {
+    let message = MozLogMessage {
+        timestamp: 1_654_041_600_000_000_000,
+        message_type: "test".to_string(),
+        logger: "test-logger".to_string(),
+        hostname: "localhost".to_string(),
+        env_version: "2.0".to_string(),
+        pid: 42,
+        severity: 5,
+        fields: hashmap! {
+            "message".to_string() => json!("flat"),
+            "code".to_string() => json!(200),
+        },
+    };
+
+    let flat: HashMap<String, Value> = message.into();
+    assert_eq!(
+        flat,
+        hashmap! {
+            "timestamp".to_string() => json!(1_654_041_600_000_000_000u64),
+            "type".to_string() => json!("test"),
+            "logger".to_string() => json!("test-logger"),
+            "hostname".to_string() => json!("localhost"),
+            "env_version".to_string() => json!("2.0"),
+            "pid".to_string() => json!(42),
+            "severity".to_string() => json!(5),
+            "message".to_string() => json!("flat"),
+            "code".to_string() => json!(200),
+        }
+    );
+}
+
+#[test]
+fn flatten_message_with_colliding_fields() {
+    let message = MozLogMessage {
+        timestamp: 1,
+        message_type: "test".to_string(),
+        logger: "test-logger".to_string(),
+        hostname: "localhost".to_string(),
+        env_version: "2.0".to_string(),
+        pid: 42,
+        severity: 5,
+        fields: hashmap! {
+            "type".to_string() => json!("field type"),
+            "pid".to_string() => json!("field pid"),
+        },
+    };
+
+    let flat: HashMap<String, Value> = message.into();
+    assert_eq!(flat.len(), 9);
+    assert_eq!(flat["type"], json!("test"));
+    assert_eq!(flat["fields.type"], json!("field type"));
+    assert_eq!(flat["pid"], json!(42));
+    assert_eq!(flat["fields.pid"], json!("field pid"));
+}
@@ -0,0 +1,52 @@
+use opentelemetry::{
+    trace::{SpanKind, Status, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_sdk::{testing::trace::InMemorySpanExporter, trace::TracerProvider};
+use serde_json::json;
+use tracing::{event, Level};
+use tracing_actix_web_mozlog::MozLogOtelBridgeLayer;
+
+use crate::utils::{log_test_with_layer, LogWatcher};
+
+#[test]
+fn bridge_reports_to_mozlog_and_otel() {
+    let exporter = InMemorySpanExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+
+    let mut log_watcher: LogWatcher = log_test_with_layer(
+        |layer| MozLogOtelBridgeLayer::new(layer, provider.tracer("test")),
+        || {
+            event!(Level::INFO, r#type = "test.info", count = 3, "all good");
+            event!(Level::ERROR, r#type = "test.error", "it broke");
+        },
+    );
+
+    let events = log_watcher.events();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].message_type, "test.info");
+    assert_eq!(events[0].fields.get("count"), Some(&json!(3)));
+    assert_eq!(events[1].message_type, "test.error");
+
+    let spans = exporter.get_finished_spans().expect("could not get spans");
+    assert_eq!(spans.len(), 2);
+
+    assert_eq!(spans[0].name, "test.info");
+    assert_eq!(spans[0].span_kind, SpanKind::Internal);
+    assert_eq!(spans[0].status, Status::Unset);
+    assert!(spans[0]
+        .attributes
+        .contains(&KeyValue::new("mozlog.severity", 5)));
+    assert!(spans[0].attributes.contains(&KeyValue::new("count", 3)));
+    assert!(spans[0]
+        .attributes
+        .contains(&KeyValue::new("message", "all good")));
+
+    assert_eq!(spans[1].name, "test.error");
+    assert_eq!(spans[1].status, Status::error("it broke"));
+    assert!(spans[1]
+        .attributes
+        .contains(&KeyValue::new("mozlog.severity", 3)));
+}
@@ -1,15 +1,30 @@
 //! Testing utils
 
+use jsonschema::JSONSchema;
+use lazy_static::lazy_static;
 use serde::de::DeserializeOwned;
+use serde_json::Value;
 use std::{
     future::Future,
     io::Write,
     sync::{Arc, Mutex},
 };
-use tracing::Subscriber;
+use tracing::{Dispatch, Level, Metadata, Subscriber};
 use tracing_actix_web_mozlog::{JsonStorageLayer, MozLogFormatLayer, MozLogMessage};
 use tracing_futures::WithSubscriber;
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, Registry};
+use tracing_subscriber::{
+    fmt::{writer::MakeWriterExt, MakeWriter},
+    layer::{Layered, SubscriberExt},
+    Layer, Registry,
+};
+
+lazy_static! {
+    /// The MozLog JSON schema, compiled for validating events.
+    pub static ref MOZLOG_SCHEMA: JSONSchema =
+        JSONSchema::compile(&PARSED_SCHEMA).expect("schema is in invalid format");
+    static ref PARSED_SCHEMA: Value =
+        serde_json::from_str(include_str!("./mozlog_schema.json")).expect("schema json is invalid");
+}
 
 /// Run a closure in an environment configured to use [`MozLogLayer`], and return
 /// a log watcher that cna make assertions about the tracing logs that occurred
@@ -26,6 +41,23 @@ where
     log_watcher
 }
 
+/// A version of [`log_test`] that formats events with the layer returned by
+/// `configure`, which is given the default test layer to customize, such as
+/// `|layer| layer.with_app_version("1.2.3")`.
+pub fn log_test_with_layer<E, L, C, F>(configure: C, test_inner: F) -> LogWatcher<E>
+where
+    E: 'static,
+    E: DeserializeOwned,
+    E: Default,
+    L: Layer<Layered<JsonStorageLayer, Registry>> + Send + Sync + 'static,
+    C: FnOnce(MozLogFormatLayer<LogWatcherWriter>) -> L,
+    F: FnOnce(),
+{
+    let (log_watcher, subscriber) = make_test_subscriber_with_layer(configure);
+    tracing::subscriber::with_default(subscriber, test_inner);
+    log_watcher
+}
+
 /// A version of [`log_test`] that can handle async inner tests.
 pub async fn log_test_async<E, F, Fut>(test_inner: F) -> LogWatcher<E>
 where
@@ -40,15 +72,29 @@ where
     log_watcher
 }
 
+/// Make a [`Dispatch`] configured like the one [`log_test`] uses, for tests
+/// that share it between tasks, and a log watcher for the events it formats.
+pub fn log_test_dispatch<E: Default + 'static>() -> (LogWatcher<E>, Dispatch) {
+    let (log_watcher, subscriber) = make_test_subscriber();
+    (log_watcher, Dispatch::new(subscriber))
+}
+
 fn make_test_subscriber<E: Default>() -> (LogWatcher<E>, impl Subscriber) {
+    make_test_subscriber_with_layer(|layer| layer)
+}
+
+fn make_test_subscriber_with_layer<E, L, C>(configure: C) -> (LogWatcher<E>, impl Subscriber)
+where
+    E: Default,
+    L: Layer<Layered<JsonStorageLayer, Registry>> + Send + Sync + 'static,
+    C: FnOnce(MozLogFormatLayer<LogWatcherWriter>) -> L,
+{
     let log_watcher: LogWatcher<E> = LogWatcher::default();
-    let log_watcher_writer = log_watcher.make_writer();
-    let formatting_layer =
-        MozLogFormatLayer::new("test-logger", move || log_watcher_writer.clone());
+    let formatting_layer = MozLogFormatLayer::new("test-logger", log_watcher.make_writer());
 
     let subscriber = Registry::default()
         .with(JsonStorageLayer)
-        .with(formatting_layer);
+        .with(configure(formatting_layer));
 
     (log_watcher, subscriber)
 }
@@ -81,6 +127,22 @@ impl<E> LogWatcher<E> {
     }
 }
 
+impl<E> LogWatcher<E> {
+    /// Make a writer that only collects events at exactly `level` into this
+    /// watcher. Writers for different levels can be combined with
+    /// [`MakeWriterExt::and`] to route each level to a different watcher.
+    pub fn make_writer_for_level(&self, level: Level) -> impl for<'a> MakeWriter<'a> {
+        let writer = self.make_writer();
+        (move || writer.clone()).with_filter(move |meta: &Metadata<'_>| *meta.level() == level)
+    }
+
+    /// The output that hasn't been converted to events yet, exactly as it
+    /// was written.
+    pub fn raw_output(&self) -> String {
+        String::from_utf8_lossy(&self.buf.lock().unwrap()).into_owned()
+    }
+}
+
 impl<E> LogWatcher<E>
 where
     E: DeserializeOwned,
@@ -118,6 +180,30 @@ where
         &self.events
     }
 
+    /// Get the first event this logger received, if any.
+    pub fn first(&mut self) -> Option<&E> {
+        self.convert_events();
+        self.events.first()
+    }
+
+    /// Get the most recent event this logger received, if any.
+    pub fn last(&mut self) -> Option<&E> {
+        self.convert_events();
+        self.events.last()
+    }
+
+    /// Get the first event this logger received, panicking with `msg` if
+    /// there are no events.
+    pub fn first_or_panic(&mut self, msg: &str) -> &E {
+        self.first().unwrap_or_else(|| panic!("{}", msg))
+    }
+
+    /// Get the most recent event this logger received, panicking with `msg`
+    /// if there are no events.
+    pub fn last_or_panic(&mut self, msg: &str) -> &E {
+        self.last().unwrap_or_else(|| panic!("{}", msg))
+    }
+
     /// Iterate through `self.buf` to convert newline separated, completed J;SON
     /// objects into [`TracingJsonEvent`] instances that are placed in
     /// `self.events`.
@@ -152,6 +238,67 @@ where
     }
 }
 
+impl LogWatcher<Value> {
+    /// Assert that every event this logger received matches the MozLog JSON schema.
+    pub fn assert_schema_valid(&mut self) {
+        for event in self.events() {
+            assert_event_matches_schema(event);
+        }
+    }
+}
+
+impl LogWatcher<MozLogMessage> {
+    /// Assert that every event this logger received matches the MozLog JSON schema.
+    pub fn assert_schema_valid(&mut self) {
+        for event in self.events() {
+            let event = serde_json::to_value(event).expect("could not serialize event");
+            assert_event_matches_schema(&event);
+        }
+    }
+
+    /// Get the events this logger received, sorted by timestamp and then by
+    /// type, so that the order doesn't depend on the order the events were
+    /// written in. Events with the same timestamp and type keep the order
+    /// they were received in.
+    pub fn events_sorted(&mut self) -> Vec<&MozLogMessage> {
+        let mut events: Vec<&MozLogMessage> = self.events().iter().collect();
+        events.sort_by_key(|event| (event.timestamp, event.message_type.clone()));
+        events
+    }
+
+    /// Get the events this logger received with a type of `message_type`,
+    /// such as `"request.summary"`, in the order they were received.
+    pub fn events_of_type(&mut self, message_type: &str) -> Vec<&MozLogMessage> {
+        self.events()
+            .iter()
+            .filter(|event| event.message_type == message_type)
+            .collect()
+    }
+
+    /// Get the events this logger received with a Syslog severity of
+    /// `severity`, in the order they were received.
+    pub fn events_of_severity(&mut self, severity: u32) -> Vec<&MozLogMessage> {
+        self.events()
+            .iter()
+            .filter(|event| event.severity == severity)
+            .collect()
+    }
+}
+
+fn assert_event_matches_schema(event: &Value) {
+    let errors = match MOZLOG_SCHEMA.validate(event) {
+        Ok(()) => None,
+        Err(errors) => Some(errors.collect::<Vec<_>>()),
+    };
+    if let Some(errors) = &errors {
+        println!("Error while validating event:\n{:#?}", event);
+        for error in errors {
+            println!("Error: {:#?}", error);
+        }
+    }
+    assert!(errors.is_none());
+}
+
 impl<E> MakeWriter<'_> for LogWatcher<E> {
     type Writer = LogWatcherWriter;
 
@@ -173,12 +320,20 @@ pub struct LogWatcherWriter {
     buf: Arc<Mutex<Vec<u8>>>,
 }
 
+impl MakeWriter<'_> for LogWatcherWriter {
+    type Writer = Self;
+
+    fn make_writer(&self) -> Self::Writer {
+        self.clone()
+    }
+}
+
 impl Write for LogWatcherWriter {
     fn write(&mut self, new_bytes: &[u8]) -> std::io::Result<usize> {
         let mut buf = self
             .buf
             .lock()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
+            .map_err(|e| std::io::Error::other(e.to_string()))?;
         buf.extend(new_bytes.iter());
         Ok(new_bytes.len())
     }
@@ -0,0 +1,34 @@
+//! Changing environment variables affects every test running in the process,
+//! so this is in its own test binary instead of `tests/all`.
+
+use std::sync::{Arc, Mutex};
+
+use tracing::{event, Level};
+use tracing_actix_web_mozlog::{JsonStorageLayer, MozLogFormatLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn default_env_filter() {
+    std::env::set_var("RUST_LOG", "warn");
+
+    let severities: Arc<Mutex<Vec<u32>>> = Arc::default();
+    let callback_severities = severities.clone();
+    let layer = MozLogFormatLayer::new("test-logger", std::io::sink)
+        .with_event_callback(Arc::new(move |message| {
+            callback_severities.lock().unwrap().push(message.severity);
+        }))
+        .with_default_env_filter();
+    let subscriber = Registry::default().with(JsonStorageLayer).with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        event!(Level::DEBUG, "debug event");
+        event!(Level::WARN, "warn event");
+    });
+
+    std::env::remove_var("RUST_LOG");
+
+    assert_eq!(
+        *severities.lock().unwrap(),
+        vec![4],
+        "should only log events enabled by RUST_LOG"
+    );
+}
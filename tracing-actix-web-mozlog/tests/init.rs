@@ -0,0 +1,17 @@
+//! Setting the global default subscriber can only be done once per process,
+//! so this is in its own test binary instead of `tests/all`.
+
+use tracing::Level;
+use tracing_actix_web_mozlog::MozLogSubscriberBuilder;
+
+#[test]
+fn init_sets_global_default_once() {
+    tracing_actix_web_mozlog::init("test-logger").expect("init should succeed");
+    assert!(
+        MozLogSubscriberBuilder::new("test-logger")
+            .with_type_required_for_level(Some(Level::INFO))
+            .init()
+            .is_err(),
+        "the global default can only be set once"
+    );
+}
@@ -0,0 +1,34 @@
+//! Changing environment variables affects every test running in the process,
+//! so this is in its own test binary instead of `tests/all`.
+
+use std::sync::{Arc, Mutex};
+
+use tracing::{event, Level};
+use tracing_actix_web_mozlog::{JsonStorageLayer, MozLogFormatLayer};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+#[test]
+fn pid_from_env() {
+    std::env::set_var("MOZLOG_TEST_WORKER_ID", "42");
+
+    let pids: Arc<Mutex<Vec<u32>>> = Arc::default();
+    let callback_pids = pids.clone();
+    let layer = MozLogFormatLayer::new("test-logger", std::io::sink)
+        .with_pid_from_env("MOZLOG_TEST_WORKER_ID")
+        .with_pid_from_env("MOZLOG_TEST_UNSET_VAR")
+        .with_event_callback(Arc::new(move |message| {
+            callback_pids.lock().unwrap().push(message.pid);
+        }));
+    let subscriber = Registry::default().with(JsonStorageLayer).with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        event!(Level::INFO, "worker event");
+    });
+
+    std::env::remove_var("MOZLOG_TEST_WORKER_ID");
+
+    assert_eq!(
+        *pids.lock().unwrap(),
+        vec![42],
+        "should use the PID from the environment"
+    );
+}